@@ -0,0 +1,9 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+// Public values committed by the keccak inclusion program.
+// Decode with abi.decode(publicValues, (KeccakInclusionToDataRootProofOutput))
+struct KeccakInclusionToDataRootProofOutput {
+    bytes32 keccak_hash;
+    bytes32 data_root;
+}
@@ -16,4 +16,7 @@ pub enum InclusionServiceError {
     
     #[error("Failed to create inclusion proof input: {0}")]
     GeneralError(String),
+
+    #[error("Failed to decode proof output: {0}")]
+    OutputDecodeError(String),
 }
\ No newline at end of file
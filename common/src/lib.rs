@@ -11,6 +11,7 @@ use tendermint_proto::{
 };
 use std::cmp::max;
 use sha3::{Keccak256, Digest};
+use alloy_sol_types::{sol, SolType};
 use celestia_types::{nmt::{NamespaceProof, NamespacedHashExt, Namespace}, blob::Blob, ExtendedHeader};
 
 mod error;
@@ -36,10 +37,12 @@ pub struct KeccakInclusionToDataRootProofInput {
     pub keccak_hash: [u8; 32],
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct KeccakInclusionToDataRootProofOutput {
-    pub keccak_hash: [u8; 32],
-    pub data_root: Vec<u8>,
+// The program commits this struct ABI encoded, so it can be decoded onchain with abi.decode
+sol!("sol/KeccakInclusionToDataRootProofOutput.sol");
+
+pub fn decode_inclusion_proof_output(public_values: &[u8]) -> Result<KeccakInclusionToDataRootProofOutput, InclusionServiceError> {
+    <KeccakInclusionToDataRootProofOutput as SolType>::abi_decode(public_values, true)
+        .map_err(|e| InclusionServiceError::OutputDecodeError(e.to_string()))
 }
 
 pub fn create_inclusion_proof_input(blob: &Blob, header: &ExtendedHeader, nmt_multiproofs: Vec<NamespaceProof>) -> Result<KeccakInclusionToDataRootProofInput, InclusionServiceError> {
@@ -1,27 +1,50 @@
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 use eq_common::{KeccakInclusionToDataRootProofInput, KeccakInclusionToDataRootProofOutput};
-use celestia_types::{blob::Blob, nmt::{MerkleHash, NamespacedHashExt}, AppVersion};
+use celestia_types::{blob::Blob, nmt::{MerkleHash, NamespacedHashExt}, AppVersion, Share};
 use nmt_rs::TmSha2Hasher;
 use tendermint::Hash as TmHash;
 use tendermint_proto::Protobuf;
 use sha3::{Digest, Keccak256};
+use alloy_sol_types::SolValue;
 
 pub fn main() {
-    println!("cycle-tracker-start: deserializing inputs");
-    let input: KeccakInclusionToDataRootProofInput = sp1_zkvm::io::read();
+    let (input, data_root, blob) = deserialize_inputs();
+    let shares = blob_to_shares(&blob);
+    verify_nmt_multiproofs(&input, &blob, &shares);
+    verify_row_root_multiproof(&input, &data_root);
+    let hash = verify_keccak_hash(&input, &blob);
+
+    let data_root: [u8; 32] = data_root.as_bytes()
+        .try_into()
+        .expect("Failed to convert data root to bytes");
+    let output = KeccakInclusionToDataRootProofOutput {
+        keccak_hash: hash.into(),
+        data_root: data_root.into(),
+    };
+    sp1_zkvm::io::commit_slice(&output.abi_encode());
+}
+
+// Each step below shows up in the execution report's cycle tracker under its function name
+
+#[sp1_derive::cycle_tracker]
+fn deserialize_inputs() -> (KeccakInclusionToDataRootProofInput, TmHash, Blob) {
+    let mut input: KeccakInclusionToDataRootProofInput = sp1_zkvm::io::read();
     let data_root = TmHash::decode_vec(&input.data_root).unwrap();
-    let mut blob: Blob = Blob::new(input.blob_namespace, input.blob_data, AppVersion::V3)
+    let mut blob: Blob = Blob::new(input.blob_namespace, std::mem::take(&mut input.blob_data), AppVersion::V3)
         .expect("Failed to create blob");
     blob.index = Some(input.blob_index);
-    println!("cycle-tracker-end: deserializing inputs");
+    (input, data_root, blob)
+}
 
-    println!("cycle-tracker-start: converting blob to shares");
-    let shares = blob.to_shares()
-        .expect("Failed to convert blob to shares");
-    println!("cycle-tracker-end: converting blob to shares");
+#[sp1_derive::cycle_tracker]
+fn blob_to_shares(blob: &Blob) -> Vec<Share> {
+    blob.to_shares()
+        .expect("Failed to convert blob to shares")
+}
 
-    println!("cycle-tracker-start: verifying NMT multiproofs");
+#[sp1_derive::cycle_tracker]
+fn verify_nmt_multiproofs(input: &KeccakInclusionToDataRootProofInput, blob: &Blob, shares: &[Share]) {
     let mut start = 0;
     for i in 0..input.nmt_multiproofs.len() {
         let proof = &input.nmt_multiproofs[i];
@@ -31,9 +54,10 @@ pub fn main() {
             .expect("NMT multiproof into row root failed verification"); // Panicking should prevent an invalid proof from being generated
         start = end;
     }
-    println!("cycle-tracker-end: verifying NMT multiproofs");
+}
 
-    println!("cycle-tracker-start: verify row root inclusion multiproof");
+#[sp1_derive::cycle_tracker]
+fn verify_row_root_multiproof(input: &KeccakInclusionToDataRootProofInput, data_root: &TmHash) {
     let tm_hasher = TmSha2Hasher {};
     let blob_row_root_hashes: Vec<[u8; 32]> = input.row_roots
         .iter()
@@ -43,19 +67,15 @@ pub fn main() {
         data_root.as_bytes().try_into().expect("Failed to convert data root to bytes"),
         &blob_row_root_hashes,
     ).expect("Row root inclusion multiproof failed verification");
-    println!("cycle-tracker-end: verify row root inclusion multiproof");
+}
 
-    println!("cycle-tracker-start: verifying keccak hash inclusion");
+#[sp1_derive::cycle_tracker]
+fn verify_keccak_hash(input: &KeccakInclusionToDataRootProofInput, blob: &Blob) -> [u8; 32] {
     let mut hasher = Keccak256::new();
     hasher.update(&blob.data);
     let hash: [u8; 32] = hasher.finalize()
         .try_into()
         .expect("Failed to convert keccak hash to array");
     assert_eq!(hash, input.keccak_hash, "Keccak hash inclusion failed verification");
-    println!("cycle-tracker-end: verifying keccak hash inclusion");
-
-    sp1_zkvm::io::commit(&KeccakInclusionToDataRootProofOutput {
-        keccak_hash: hash,
-        data_root: data_root.as_bytes().to_vec(),
-    });
+    hash
 }
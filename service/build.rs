@@ -2,7 +2,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .build_server(true)
         .build_client(true)
+        // ProofMode is part of the version 0 job keys, see storage.rs
+        .type_attribute("eqs.ProofMode", "#[derive(serde::Serialize, serde::Deserialize)]")
+        // Priority is stored in JobRecord
+        .type_attribute("eqs.Priority", "#[derive(serde::Serialize, serde::Deserialize)]")
         .out_dir("src/generated")
+        // For gRPC server reflection
+        .file_descriptor_set_path("src/generated/eqs_descriptor.bin")
         .compile_protos(
             &["proto/eqservice.proto"],
             &["proto/"]
@@ -0,0 +1,223 @@
+use std::ops::Bound;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use tokio::sync::broadcast;
+use tracing::Span;
+use subtle::ConstantTimeEq;
+
+use crate::eqs::admin_server::Admin;
+use crate::eqs::{GetKeccakInclusionRequest, JobInfo, JobInput, CompactRequest, CompactResponse, ListJobsRequest, ListJobsResponse};
+use crate::gc::{collect_garbage, RetentionPolicy};
+use crate::job::{resolve_job, JobStatus};
+use crate::node::Networks;
+use crate::queue::JobQueue;
+use crate::storage::{self, JobStore, StorageError};
+use crate::worker::Worker;
+
+const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+pub struct AdminService {
+    storage: Arc<dyn JobStore>,
+    worker: Worker,
+    queue: Arc<JobQueue>,
+    networks: Arc<Networks>,
+    retention_policy: RetentionPolicy,
+    job_updates: broadcast::Sender<Vec<u8>>,
+}
+
+impl AdminService {
+    pub fn new(
+        storage: Arc<dyn JobStore>,
+        worker: Worker,
+        queue: Arc<JobQueue>,
+        networks: Arc<Networks>,
+        retention_policy: RetentionPolicy,
+        job_updates: broadcast::Sender<Vec<u8>>,
+    ) -> Self {
+        AdminService { storage, worker, queue, networks, retention_policy, job_updates }
+    }
+}
+
+// Rejects any request that doesn't carry the admin token
+pub fn check_admin_token(token: String) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+    move |request: Request<()>| {
+        match request.metadata().get(ADMIN_TOKEN_HEADER) {
+            // Constant time, so the token can't be guessed a byte at a time from response times
+            Some(value) if bool::from(value.as_bytes().ct_eq(token.as_bytes())) => Ok(request),
+            _ => Err(Status::unauthenticated("Missing or invalid admin token")),
+        }
+    }
+}
+
+fn list_jobs_page(storage: &dyn JobStore, request: &ListJobsRequest) -> Result<ListJobsResponse, Status> {
+    let page_size = match request.page_size {
+        0 => DEFAULT_PAGE_SIZE,
+        page_size => (page_size as usize).min(MAX_PAGE_SIZE),
+    };
+    // Jobs are stored in height order, so we can skip straight to min_height
+    let start = match request.page_token.as_str() {
+        "" => Bound::Included(storage::height_key(request.min_height.unwrap_or_default())),
+        token => Bound::Excluded(hex::decode(token).map_err(|_| Status::invalid_argument("Invalid page token"))?),
+    };
+
+    let mut jobs = Vec::new();
+    let mut last_key = Vec::new();
+    let mut next_page_token = String::new();
+    for entry in storage.jobs_from(start) {
+        let (key, job, record) = entry?;
+        if request.max_height.is_some_and(|max| job.height > max) {
+            break;
+        }
+        if !request.namespace.is_empty() && job.namespace != request.namespace {
+            continue;
+        }
+        if !request.stages.is_empty() && !request.stages.contains(&(record.status.stage() as i32)) {
+            continue;
+        }
+
+        // There's at least one more, the next page starts after the last one we return
+        if jobs.len() == page_size {
+            next_page_token = hex::encode(&last_key);
+            break;
+        }
+        jobs.push(record.info(&job));
+        last_key = key;
+    }
+    Ok(ListJobsResponse { jobs, next_page_token })
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    #[tracing::instrument(skip_all)]
+    async fn list_jobs(
+        &self,
+        request: Request<ListJobsRequest>,
+    ) -> Result<Response<ListJobsResponse>, Status> {
+        let request = request.into_inner();
+        let storage = self.storage.clone();
+        // With filters this can go through every job, keep it off the async worker threads
+        let response = tokio::task::spawn_blocking(move || list_jobs_page(storage.as_ref(), &request))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))??;
+        Ok(Response::new(response))
+    }
+
+    #[tracing::instrument(skip_all, fields(job_id))]
+    async fn get_job(
+        &self,
+        request: Request<GetKeccakInclusionRequest>,
+    ) -> Result<Response<JobInfo>, Status> {
+        let (job, key) = resolve_job(self.storage.as_ref(), &request.into_inner(), &self.networks)?;
+        let record = self.storage.get_job(&key)?
+            .ok_or_else(|| Status::not_found("Job not found"))?;
+        Span::current().record("job_id", record.job_id.as_str());
+        Ok(Response::new(record.info(&job)))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<GetKeccakInclusionRequest>,
+    ) -> Result<Response<JobInfo>, Status> {
+        let (job, key) = resolve_job(self.storage.as_ref(), &request.into_inner(), &self.networks)?;
+        let record = self.worker.cancel(&key)?;
+        Ok(Response::new(record.info(&job)))
+    }
+
+    async fn retry_job(
+        &self,
+        request: Request<GetKeccakInclusionRequest>,
+    ) -> Result<Response<JobInfo>, Status> {
+        let (job, key) = resolve_job(self.storage.as_ref(), &request.into_inner(), &self.networks)?;
+        let record = self.storage.get_job(&key)?
+            .ok_or_else(|| Status::not_found("Job not found"))?;
+        if !record.status.is_terminal() {
+            self.worker.cancel(&key)?;
+        }
+
+        self.worker.set_status(&key, JobStatus::Queued)
+            .map_err(Status::internal)?;
+        // Operators can always retry, even when the queue is full
+        self.queue.push(job.clone(), record.priority);
+
+        let record = self.storage.get_job(&key)?
+            .ok_or_else(|| Status::not_found("Job not found"))?;
+        Ok(Response::new(record.info(&job)))
+    }
+
+    async fn get_job_input(
+        &self,
+        request: Request<GetKeccakInclusionRequest>,
+    ) -> Result<Response<JobInput>, Status> {
+        let (_, key) = resolve_job(self.storage.as_ref(), &request.into_inner(), &self.networks)?;
+        let input = self.storage.get_input(&key)?
+            .ok_or_else(|| Status::not_found("No input stored for job"))?;
+        let input_json = serde_json::to_string_pretty(&input)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(JobInput { input_json }))
+    }
+
+    async fn compact(
+        &self,
+        _request: Request<CompactRequest>,
+    ) -> Result<Response<CompactResponse>, Status> {
+        let (storage, policy, job_updates) = (self.storage.clone(), self.retention_policy.clone(), self.job_updates.clone());
+        // Goes through every job and waits on the disk, so keep it off the async runtime
+        let response = tokio::task::spawn_blocking(move || {
+            let report = collect_garbage(storage.as_ref(), &policy, &job_updates)?;
+            storage.flush()?;
+            Ok::<_, StorageError>(CompactResponse {
+                pruned_proofs: report.pruned_proofs,
+                freed_bytes: report.freed_bytes,
+                size_on_disk: storage.size_on_disk()?,
+            })
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))??;
+        Ok(Response::new(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eqs::Priority;
+    use crate::job::JobRecord;
+    use crate::storage::MemoryStore;
+    use crate::test_utils;
+
+    fn store_with_jobs(count: u64) -> MemoryStore {
+        let store = MemoryStore::new();
+        for height in 0..count {
+            let key = storage::job_key(&test_utils::job(height));
+            store.insert_job(&key, &JobRecord::queued(&key, Priority::Normal)).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn list_jobs_page_caps_page_size() {
+        let store = store_with_jobs(1005);
+        let request = ListJobsRequest { page_size: 5000, ..Default::default() };
+        let response = list_jobs_page(&store, &request).unwrap();
+        assert_eq!(response.jobs.len(), MAX_PAGE_SIZE);
+        assert!(!response.next_page_token.is_empty());
+    }
+
+    #[test]
+    fn list_jobs_page_covers_every_job() {
+        let store = store_with_jobs(7);
+        let mut heights = Vec::new();
+        let mut request = ListJobsRequest { page_size: 3, ..Default::default() };
+        loop {
+            let response = list_jobs_page(&store, &request).unwrap();
+            heights.extend(response.jobs.iter().map(|job| job.request.as_ref().unwrap().height));
+            if response.next_page_token.is_empty() {
+                break;
+            }
+            request.page_token = response.next_page_token;
+        }
+        assert_eq!(heights, (0..7).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use serde::Deserialize;
+use thiserror::Error;
+use tonic::{Request, Status};
+
+use crate::eqs::Priority;
+use crate::job::unix_now;
+use crate::queue;
+use crate::storage::JobStore;
+
+const API_KEY_HEADER: &str = "x-api-key";
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/*
+    API keys are read from a JSON file like:
+    {
+        "clients": [
+            {
+                "name": "bridge",
+                "key": "...",
+                "proofs_per_day": 1000,
+                "max_concurrent_jobs": 20,
+                "requests_per_second": 5,
+                "burst": 20,
+                "max_priority": "Normal"
+            }
+        ]
+    }
+    Every limit is optional, a missing one means unlimited. Usage is only tracked in memory,
+    so it starts over when the service restarts.
+*/
+
+#[derive(Error, Debug)]
+pub enum ApiKeysError {
+    #[error("Failed to read API keys file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid API keys file: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("API key for client {0} is also used by another client")]
+    DuplicateKey(String),
+}
+
+#[derive(Deserialize)]
+struct ApiKeysConfig {
+    clients: Vec<ClientConfig>,
+}
+
+#[derive(Deserialize)]
+struct ClientConfig {
+    name: String,
+    key: String,
+    // New proofs the client can request per UTC day, requests for existing jobs are free
+    proofs_per_day: Option<u32>,
+    // Jobs requested by the client that can be unfinished at once
+    max_concurrent_jobs: Option<usize>,
+    // Sustained request rate, and how many requests can be made at once on top of it.
+    // The burst defaults to one second's worth of requests.
+    requests_per_second: Option<f64>,
+    burst: Option<f64>,
+    // The highest priority the client can request jobs with
+    max_priority: Option<Priority>,
+}
+
+pub struct ApiKeys {
+    clients: HashMap<String, Arc<ApiClient>>,
+}
+
+impl ApiKeys {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ApiKeysError> {
+        let config: ApiKeysConfig = serde_json::from_slice(&std::fs::read(path)?)?;
+        let mut clients = HashMap::new();
+        for client in config.clients {
+            let key = client.key.clone();
+            let name = client.name.clone();
+            if clients.insert(key, Arc::new(ApiClient::new(client))).is_some() {
+                return Err(ApiKeysError::DuplicateKey(name));
+            }
+        }
+        Ok(ApiKeys { clients })
+    }
+}
+
+// A client authenticated by its API key, check_api_key adds it to the request extensions
+pub struct ApiClient {
+    config: ClientConfig,
+    usage: Mutex<Usage>,
+}
+
+struct Usage {
+    // Token bucket for the rate limit
+    tokens: f64,
+    refilled_at: Instant,
+    // Days since the Unix epoch, and the proofs requested that day
+    day: u64,
+    proofs: u32,
+    // Keys of jobs requested by the client that may still be unfinished
+    active_jobs: HashSet<Vec<u8>>,
+    // The ones of those that are charged but not stored yet, so they don't look finished
+    pending_jobs: HashSet<Vec<u8>>,
+}
+
+impl ApiClient {
+    fn new(config: ClientConfig) -> Self {
+        let usage = Usage {
+            tokens: config.burst(),
+            refilled_at: Instant::now(),
+            day: unix_now() / SECS_PER_DAY,
+            proofs: 0,
+            active_jobs: HashSet::new(),
+            pending_jobs: HashSet::new(),
+        };
+        ApiClient { config, usage: Mutex::new(usage) }
+    }
+
+    fn check_rate_limit(&self) -> Result<(), Status> {
+        let Some(rate) = self.config.requests_per_second else {
+            return Ok(());
+        };
+        let mut usage = self.usage.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(usage.refilled_at).as_secs_f64();
+        usage.tokens = (usage.tokens + elapsed * rate).min(self.config.burst());
+        usage.refilled_at = now;
+
+        if usage.tokens < 1.0 {
+            return Err(Status::resource_exhausted("Rate limit exceeded, slow down"));
+        }
+        usage.tokens -= 1.0;
+        Ok(())
+    }
+
+    // Counts a new job against the client's quotas, failing if it would go over either.
+    // The job counts as unfinished from here on, even before it's stored.
+    pub fn charge_job(&self, key: &[u8], storage: &dyn JobStore) -> Result<JobCharge<'_>, Status> {
+        let mut usage = self.usage.lock().unwrap();
+        let today = unix_now() / SECS_PER_DAY;
+        if usage.day != today {
+            usage.day = today;
+            usage.proofs = 0;
+        }
+        if let Some(limit) = self.config.proofs_per_day {
+            if usage.proofs >= limit {
+                return Err(Status::resource_exhausted(format!("Daily quota of {} proofs reached", limit)));
+            }
+        }
+
+        if let Some(limit) = self.config.max_concurrent_jobs {
+            // Forget jobs that have finished since we last looked
+            let mut finished = Vec::new();
+            for job_key in usage.active_jobs.difference(&usage.pending_jobs) {
+                if storage.get_job(job_key)?.is_none_or(|record| record.status.is_terminal()) {
+                    finished.push(job_key.clone());
+                }
+            }
+            for job_key in finished {
+                usage.active_jobs.remove(&job_key);
+            }
+            if usage.active_jobs.len() >= limit {
+                return Err(Status::resource_exhausted(format!("Limit of {} unfinished jobs reached", limit)));
+            }
+        }
+
+        usage.proofs += 1;
+        let added = usage.active_jobs.insert(key.to_vec());
+        if added {
+            usage.pending_jobs.insert(key.to_vec());
+        }
+        Ok(JobCharge { client: self, key: key.to_vec(), added, kept: false })
+    }
+
+    // Fails if the client isn't allowed to request jobs with this priority
+    pub fn check_priority(&self, priority: Priority) -> Result<(), Status> {
+        match self.config.max_priority {
+            Some(max) if queue::lane(priority) < queue::lane(max) => Err(Status::permission_denied(format!(
+                "Priority {} is above this client's maximum of {}", priority.as_str_name(), max.as_str_name()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+}
+
+// A job charged to a client, refunded when dropped unless it's kept
+pub struct JobCharge<'a> {
+    client: &'a ApiClient,
+    key: Vec<u8>,
+    // Whether the job wasn't one of the client's unfinished jobs already
+    added: bool,
+    kept: bool,
+}
+
+impl JobCharge<'_> {
+    // For once the job is stored and queued, it counts as unfinished until its record says otherwise
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for JobCharge<'_> {
+    fn drop(&mut self) {
+        let mut usage = self.client.usage.lock().unwrap();
+        if self.added {
+            usage.pending_jobs.remove(&self.key);
+        }
+        // The job turned out not to need proving after all, e.g. someone else queued it first
+        if !self.kept {
+            usage.proofs = usage.proofs.saturating_sub(1);
+            if self.added {
+                usage.active_jobs.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl ClientConfig {
+    fn burst(&self) -> f64 {
+        self.burst.or(self.requests_per_second).unwrap_or_default().max(1.0)
+    }
+}
+
+// Rejects requests without a known API key or over the client's rate limit. Without
+// any configured keys the service is open to anyone, like it used to be.
+pub fn check_api_key(api_keys: Option<Arc<ApiKeys>>) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+    move |mut request: Request<()>| {
+        let Some(api_keys) = &api_keys else {
+            return Ok(request);
+        };
+        let client = request.metadata().get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|key| api_keys.clients.get(key))
+            .cloned()
+            .ok_or_else(|| Status::unauthenticated("Missing or invalid API key"))?;
+        client.check_rate_limit()?;
+        request.extensions_mut().insert(client);
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{JobRecord, JobStatus};
+    use crate::storage::{job_key, MemoryStore};
+    use crate::test_utils;
+
+    fn client(config: serde_json::Value) -> ApiClient {
+        let mut config = config;
+        config["name"] = "test".into();
+        config["key"] = "secret".into();
+        ApiClient::new(serde_json::from_value(config).unwrap())
+    }
+
+    #[test]
+    fn limits_priority() {
+        let limited = client(serde_json::json!({ "max_priority": "Normal" }));
+        assert!(limited.check_priority(Priority::Low).is_ok());
+        assert!(limited.check_priority(Priority::Normal).is_ok());
+        assert_eq!(limited.check_priority(Priority::High).unwrap_err().code(), tonic::Code::PermissionDenied);
+
+        let unlimited = client(serde_json::json!({}));
+        assert!(unlimited.check_priority(Priority::High).is_ok());
+    }
+
+    #[test]
+    fn limits_proofs_per_day() {
+        let store = MemoryStore::new();
+        let client = client(serde_json::json!({ "proofs_per_day": 2 }));
+        let keys: Vec<_> = (0..3).map(|height| job_key(&test_utils::job(height))).collect();
+        client.charge_job(&keys[0], &store).unwrap().keep();
+        let refunded = client.charge_job(&keys[1], &store).unwrap();
+        assert!(matches!(client.charge_job(&keys[2], &store), Err(status) if status.code() == tonic::Code::ResourceExhausted));
+
+        drop(refunded);
+        client.charge_job(&keys[2], &store).unwrap();
+    }
+
+    #[test]
+    fn limits_unfinished_jobs() {
+        let store = MemoryStore::new();
+        let client = client(serde_json::json!({ "max_concurrent_jobs": 1 }));
+        let first = job_key(&test_utils::job(1));
+        let second = job_key(&test_utils::job(2));
+        let mut record = JobRecord::queued(&first, Priority::Normal);
+        store.put_job(&first, &record).unwrap();
+        client.charge_job(&first, &store).unwrap().keep();
+        assert!(client.charge_job(&second, &store).is_err());
+
+        record.update(JobStatus::Completed);
+        store.put_job(&first, &record).unwrap();
+        client.charge_job(&second, &store).unwrap();
+    }
+
+    #[test]
+    fn counts_jobs_that_arent_stored_yet() {
+        let store = MemoryStore::new();
+        let client = client(serde_json::json!({ "max_concurrent_jobs": 1 }));
+        let keys: Vec<_> = (1..=2).map(|height| job_key(&test_utils::job(height))).collect();
+        let barrier = std::sync::Barrier::new(2);
+
+        // Both charges are held at once, neither job is in the store yet
+        let charged = std::thread::scope(|scope| {
+            let charges: Vec<_> = keys.iter().map(|key| scope.spawn(|| {
+                let charge = client.charge_job(key, &store);
+                barrier.wait();
+                charge.is_ok()
+            })).collect();
+            charges.into_iter().map(|charge| charge.join().unwrap()).filter(|&ok| ok).count()
+        });
+        assert_eq!(charged, 1);
+
+        // Deduplicated jobs are given back
+        let charge = client.charge_job(&keys[0], &store).unwrap();
+        assert!(client.charge_job(&keys[1], &store).is_err());
+        drop(charge);
+        client.charge_job(&keys[1], &store).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+use std::io::{BufRead, Write};
+use serde::{Serialize, Deserialize};
+use sp1_sdk::{CpuProver, Prover, SP1Proof, SP1ProofWithPublicValues, SP1VerifyingKey};
+use thiserror::Error;
+use tracing::warn;
+
+use eq_common::{KeccakInclusionToDataRootProofInput, decode_inclusion_proof_output};
+use crate::eqs::{GetKeccakInclusionRequest, Priority, ProofMode};
+use crate::job::{Job, JobRecord, JobStatus};
+use crate::storage::{self, JobStore, StorageError};
+
+/*
+    Bundles are JSON Lines files with one completed job per line, so they can be
+    concatenated, split and inspected with ordinary tools. Binary fields are hex encoded.
+    The proof itself is bincode encoded, the same as in GetKeccakInclusionResponse.
+*/
+
+const BUNDLE_FORMAT: &str = "eq-keccak-inclusion";
+// Version 2 added chain_id, version 1 records don't say which network they're for
+const BUNDLE_VERSION: u32 = 2;
+
+#[derive(Error, Debug)]
+pub enum BundleError {
+    #[error("Failed to read or write bundle: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid bundle record: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid hex: {0}")]
+    Hex(#[from] hex::FromHexError),
+
+    #[error("Failed to decode proof: {0}")]
+    Codec(#[from] bincode::Error),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+
+    #[error("{0}")]
+    Invalid(String),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProofBundle {
+    pub format: String,
+    pub version: u32,
+    pub job_id: String,
+    #[serde(default)]
+    pub chain_id: String,
+    pub height: u64,
+    pub namespace: String,
+    pub commitment: String,
+    pub proof_mode: ProofMode,
+    pub vkey_hash: String,
+    pub public_values: String,
+    pub proof: String,
+    // Missing for jobs proved before inputs were stored
+    pub input: Option<KeccakInclusionToDataRootProofInput>,
+    // Unix timestamps in seconds
+    pub created_at: u64,
+    pub completed_at: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: u64,
+    // Already complete in this store
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+// Writes every completed job in the store, returning how many were written
+pub fn export(storage: &dyn JobStore, vkey_hash: &str, mut writer: impl Write) -> Result<u64, BundleError> {
+    let mut exported = 0;
+    for entry in storage.jobs_from(std::ops::Bound::Unbounded) {
+        let (key, job, record) = entry?;
+        if !matches!(record.status, JobStatus::Completed) {
+            continue;
+        }
+        let proof = storage.get_proof(&key)?
+            .ok_or_else(|| BundleError::Invalid(format!("Proof for completed job {} is missing", record.job_id)))?;
+
+        let bundle = ProofBundle {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            job_id: record.job_id,
+            chain_id: job.chain_id,
+            height: job.height,
+            namespace: hex::encode(&job.namespace),
+            commitment: hex::encode(&job.commitment),
+            proof_mode: job.proof_mode,
+            vkey_hash: vkey_hash.to_string(),
+            public_values: hex::encode(proof.public_values.as_slice()),
+            proof: hex::encode(bincode::serialize(&proof)?),
+            input: storage.get_input(&key)?,
+            created_at: record.created_at,
+            completed_at: record.updated_at,
+        };
+        serde_json::to_writer(&mut writer, &bundle)?;
+        writer.write_all(b"\n")?;
+        exported += 1;
+    }
+    writer.flush()?;
+    Ok(exported)
+}
+
+// Loads every record from the bundle whose proof verifies against our program. Records that
+// don't verify are reported and skipped so one bad record doesn't hold up the rest.
+pub fn import(
+    storage: &dyn JobStore,
+    prover: &CpuProver,
+    vk: &SP1VerifyingKey,
+    vkey_hash: &str,
+    legacy_chain_id: Option<&str>,
+    reader: impl BufRead,
+) -> Result<ImportReport, BundleError> {
+    let mut report = ImportReport::default();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match import_record(storage, prover, vk, vkey_hash, legacy_chain_id, &line) {
+            Ok(true) => report.imported += 1,
+            Ok(false) => report.skipped += 1,
+            Err(e) => {
+                warn!("Skipping bundle record on line {}: {}", index + 1, e);
+                report.failed += 1;
+            }
+        }
+    }
+    storage.flush()?;
+    Ok(report)
+}
+
+// Returns whether the record was imported, as opposed to already being complete here
+fn import_record(
+    storage: &dyn JobStore,
+    prover: &CpuProver,
+    vk: &SP1VerifyingKey,
+    vkey_hash: &str,
+    legacy_chain_id: Option<&str>,
+    line: &str,
+) -> Result<bool, BundleError> {
+    let bundle: ProofBundle = serde_json::from_str(line)?;
+    if bundle.format != BUNDLE_FORMAT || !(1..=BUNDLE_VERSION).contains(&bundle.version) {
+        return Err(BundleError::Invalid(format!("Unsupported bundle format {} version {}", bundle.format, bundle.version)));
+    }
+    if bundle.vkey_hash != vkey_hash {
+        return Err(BundleError::Invalid(format!("Proof is for program {}, expected {}", bundle.vkey_hash, vkey_hash)));
+    }
+
+    let chain_id = match (bundle.chain_id.as_str(), legacy_chain_id) {
+        ("", Some(legacy_chain_id)) => legacy_chain_id,
+        ("", None) => return Err(BundleError::Invalid("Record doesn't say which network it's for, set --legacy-chain-id to import it".to_string())),
+        (chain_id, _) => chain_id,
+    };
+
+    // Same checks as a request for the job would get
+    let job = Job::from_request(&GetKeccakInclusionRequest {
+        commitment: hex::decode(&bundle.commitment)?,
+        namespace: hex::decode(&bundle.namespace)?,
+        height: bundle.height,
+        proof_mode: bundle.proof_mode as i32,
+        job_id: String::new(),
+        priority: Priority::Normal as i32,
+        chain_id: chain_id.to_string(),
+    }, chain_id).map_err(|e| BundleError::Invalid(e.message().to_string()))?;
+    let key = storage::job_key(&job);
+
+    let proof: SP1ProofWithPublicValues = bincode::deserialize(&hex::decode(&bundle.proof)?)?;
+    let proof_mode = match &proof.proof {
+        SP1Proof::Groth16(_) => ProofMode::Groth16,
+        SP1Proof::Plonk(_) => ProofMode::Plonk,
+        SP1Proof::Compressed(_) => ProofMode::Compressed,
+        SP1Proof::Core(_) => ProofMode::Core,
+    };
+    if proof_mode != job.proof_mode {
+        return Err(BundleError::Invalid(format!("Proof is {:?}, expected {:?}", proof_mode, job.proof_mode)));
+    }
+    if hex::decode(&bundle.public_values)? != proof.public_values.as_slice() {
+        return Err(BundleError::Invalid("Public values don't match the proof".to_string()));
+    }
+    prover.verify(&proof, vk)
+        .map_err(|e| BundleError::Invalid(format!("Proof failed to verify: {}", e)))?;
+
+    // The proof only shows the keccak hash is included under the data root,
+    // make sure that's the blob the input (if any) says it is
+    let output = decode_inclusion_proof_output(proof.public_values.as_slice())
+        .map_err(|e| BundleError::Invalid(e.to_string()))?;
+    if let Some(input) = &bundle.input {
+        if input.keccak_hash != output.keccak_hash.0 || input.data_root != output.data_root.as_slice() {
+            return Err(BundleError::Invalid("Input doesn't match the proof's public values".to_string()));
+        }
+    }
+
+    let existing = storage.get_job(&key)?;
+    if existing.as_ref().is_some_and(|record| matches!(record.status, JobStatus::Completed)) {
+        return Ok(false);
+    }
+
+    // Store the proof before the record, so a complete job always has one
+    storage.put_proof(&key, &proof)?;
+    if let Some(input) = &bundle.input {
+        storage.put_input(&key, input)?;
+    }
+    let mut record = JobRecord::queued(&key, Priority::Normal);
+    record.created_at = bundle.created_at;
+    record.update(JobStatus::Completed);
+    match existing {
+        Some(existing) => {
+            record.job_id = existing.job_id.clone();
+            record.attempts = existing.attempts;
+            if !storage.replace_job(&key, &existing, &record)? {
+                return Err(BundleError::Invalid("Job changed while importing it".to_string()));
+            }
+        }
+        None => {
+            if storage.insert_job(&key, &record)?.is_some() {
+                return Err(BundleError::Invalid("Job changed while importing it".to_string()));
+            }
+        }
+    }
+    Ok(true)
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderMap},
+    response::{Html, IntoResponse},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use tonic::Status;
+
+use crate::auth::ApiKeys;
+use crate::eqs::JobStage;
+use crate::gateway::{authenticate, ApiError};
+use crate::node::{Networks, NodeClient};
+use crate::queue::JobQueue;
+use crate::storage::{JobStore, StorageError};
+
+// Built into the binary so the service doesn't depend on where it's run from
+const INDEX_HTML: &str = include_str!("../static/index.html");
+const DASHBOARD_JS: &str = include_str!("../static/dashboard.js");
+const DASHBOARD_CSS: &str = include_str!("../static/dashboard.css");
+
+#[derive(Clone)]
+pub struct Dashboard {
+    pub storage: Arc<dyn JobStore>,
+    pub networks: Arc<Networks>,
+    pub queue: Arc<JobQueue>,
+    pub api_keys: Option<Arc<ApiKeys>>,
+}
+
+// The web UI at /, and the status it shows at /v1/status. Everything else it
+// shows comes from the gateway routes.
+pub fn routes(dashboard: Dashboard) -> Router {
+    Router::new()
+        .route("/", get(|| async { Html(INDEX_HTML) }))
+        .route("/dashboard.js", get(|| async { ([(CONTENT_TYPE, "text/javascript")], DASHBOARD_JS) }))
+        .route("/dashboard.css", get(|| async { ([(CONTENT_TYPE, "text/css")], DASHBOARD_CSS) }))
+        .route("/v1/status", get(status))
+        .with_state(dashboard)
+}
+
+#[derive(Serialize)]
+struct StatusJson {
+    // By chain ID
+    nodes: BTreeMap<String, NodeStatus>,
+    queue_length: usize,
+    // Jobs by lowercase stage name
+    jobs: BTreeMap<String, u64>,
+    // Proofs requested from the prover network by lowercase proof mode. A job that was
+    // retried from scratch only counts its latest request.
+    prover_requests: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+struct NodeStatus {
+    local_height: Option<u64>,
+    network_height: Option<u64>,
+    synced: bool,
+    error: Option<String>,
+}
+
+async fn status(
+    State(dashboard): State<Dashboard>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    authenticate(&dashboard.api_keys, &headers, ())?;
+
+    let storage = dashboard.storage.clone();
+    // Goes through every job, keep it off the async worker threads
+    let (jobs, prover_requests) = tokio::task::spawn_blocking(move || job_counts(storage.as_ref()))
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(Status::from)?;
+
+    // All at once, a node we're reconnecting to can take a while to answer
+    let mut checks = tokio::task::JoinSet::new();
+    for node in dashboard.networks.nodes() {
+        let node = node.clone();
+        checks.spawn(async move { (node.chain_id().to_string(), node_status(&node).await) });
+    }
+    let mut nodes = BTreeMap::new();
+    while let Some(check) = checks.join_next().await {
+        let (chain_id, status) = check.map_err(|e| Status::internal(e.to_string()))?;
+        nodes.insert(chain_id, status);
+    }
+
+    Ok(Json(StatusJson {
+        nodes,
+        queue_length: dashboard.queue.len(),
+        jobs,
+        prover_requests,
+    }))
+}
+
+type Counts = BTreeMap<String, u64>;
+
+fn job_counts(storage: &dyn JobStore) -> Result<(Counts, Counts), StorageError> {
+    // Every stage shows up, even with no jobs in it
+    let mut jobs: Counts = [JobStage::Queued, JobStage::Fetching, JobStage::Proving, JobStage::Complete, JobStage::Failed, JobStage::Pruned]
+        .iter()
+        .map(|stage| (stage.as_str_name().to_lowercase(), 0))
+        .collect();
+    let mut prover_requests = Counts::new();
+    for entry in storage.jobs_from(std::ops::Bound::Unbounded) {
+        let (_, job, record) = entry?;
+        *jobs.entry(record.status.stage().as_str_name().to_lowercase()).or_default() += 1;
+        if record.proof_id.is_some() {
+            *prover_requests.entry(job.proof_mode.as_str_name().to_lowercase()).or_default() += 1;
+        }
+    }
+    Ok((jobs, prover_requests))
+}
+
+async fn node_status(node: &NodeClient) -> NodeStatus {
+    let client = match node.client().await {
+        Ok(client) => client,
+        Err(error) => return NodeStatus {
+            local_height: None,
+            network_height: None,
+            synced: false,
+            error: Some(error.to_string()),
+        },
+    };
+    let local_head = client.local_head().await;
+    let network_head = client.network_head().await;
+    match (local_head, network_head) {
+        (Ok(local_head), Ok(network_head)) => {
+            let local_height = local_head.height().value();
+            let network_height = network_head.height().value();
+            NodeStatus {
+                local_height: Some(local_height),
+                network_height: Some(network_height),
+                synced: local_height >= network_height,
+                error: None,
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => NodeStatus {
+            local_height: None,
+            network_height: None,
+            synced: false,
+            error: Some(e.to_string()),
+        },
+    }
+}
@@ -0,0 +1,554 @@
+use std::convert::Infallible;
+use std::fs::File;
+use std::future::Future;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::Path as FsPath;
+use std::sync::Arc;
+use std::time::Duration;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio_rustls::rustls::{self, server::WebPkiClientVerifier, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{metadata::MetadataMap, Code, Extensions, Request, Status};
+use tracing::warn;
+
+use crate::InclusionService;
+use crate::admin::{check_admin_token, AdminService};
+use crate::auth::{check_api_key, ApiKeys};
+use crate::eqs::admin_server::Admin;
+use crate::eqs::inclusion_server::Inclusion;
+use crate::eqs::{
+    GetKeccakInclusionRequest, GetKeccakInclusionResponse, JobInfo, JobStage, KeccakInclusionProof,
+    ListJobsRequest, ListJobsResponse, Priority, ProofMode, WatchKeccakInclusionResponse,
+    get_keccak_inclusion_response::{ResponseValue, Status as ResponseStatus},
+    watch_keccak_inclusion_response::ResponseValue as WatchResponseValue,
+};
+
+/*
+    HTTP/JSON mirror of the Inclusion service, for clients that can't speak gRPC:
+    GET /v1/keccak-inclusion/{height}/{namespace}/{commitment}?proof_mode=groth16&priority=high
+    GET /v1/keccak-inclusion/{height}/{namespace}/{commitment}/watch   (server-sent events)
+    GET /v1/jobs/{job_id}/proof   (same as the keccak-inclusion request the job was created by)
+
+    And of the Admin service's ListJobs and GetJob, which need the admin token in the
+    x-admin-token header and are only served when there is one:
+    GET /v1/jobs?stages=queued,proving&min_height=&max_height=&namespace=&page_size=&page_token=
+    GET /v1/jobs/{job_id}
+
+    Byte fields are 0x prefixed hex both ways (the prefix is optional in requests), enums are
+    their lowercase proto names. API keys go in the x-api-key header, like the gRPC metadata.
+*/
+
+#[derive(Clone)]
+struct Gateway {
+    service: Arc<InclusionService>,
+    api_keys: Option<Arc<ApiKeys>>,
+    admin: Option<AdminRoutes>,
+}
+
+// The admin service and the token its routes need
+#[derive(Clone)]
+pub struct AdminRoutes {
+    pub service: Arc<AdminService>,
+    pub token: String,
+}
+
+impl Gateway {
+    fn request<T>(&self, headers: &HeaderMap, message: T) -> Result<Request<T>, Status> {
+        authenticate(&self.api_keys, headers, message)
+    }
+
+    // Runs the same admin token check as the gRPC server
+    fn admin_request<T>(&self, headers: &HeaderMap, message: T) -> Result<(&AdminService, Request<T>), Status> {
+        let admin = self.admin.as_ref()
+            .ok_or_else(|| Status::not_found("Admin routes are disabled, there's no admin token"))?;
+        let request = Request::from_parts(MetadataMap::from_headers(headers.clone()), Extensions::default(), ());
+        let (metadata, extensions, ()) = check_admin_token(admin.token.clone())(request)?.into_parts();
+        Ok((admin.service.as_ref(), Request::from_parts(metadata, extensions, message)))
+    }
+}
+
+// Runs the same API key check as the gRPC server, turning the HTTP request into a gRPC one
+pub fn authenticate<T>(api_keys: &Option<Arc<ApiKeys>>, headers: &HeaderMap, message: T) -> Result<Request<T>, Status> {
+    let request = Request::from_parts(MetadataMap::from_headers(headers.clone()), Extensions::default(), ());
+    let (metadata, extensions, ()) = check_api_key(api_keys.clone())(request)?.into_parts();
+    Ok(Request::from_parts(metadata, extensions, message))
+}
+
+// Clients that connect but never finish the handshake are dropped after this long
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Serves the gateway along with any extra routes, e.g. the dashboard, until `shutdown` completes.
+// Over TLS if `tls` is set, plaintext otherwise.
+pub async fn serve(
+    addr: SocketAddr,
+    tls: Option<Arc<ServerConfig>>,
+    service: Arc<InclusionService>,
+    api_keys: Option<Arc<ApiKeys>>,
+    admin: Option<AdminRoutes>,
+    extra_routes: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let app = Router::new()
+        .route("/v1/keccak-inclusion/:height/:namespace/:commitment", get(get_keccak_inclusion))
+        .route("/v1/keccak-inclusion/:height/:namespace/:commitment/watch", get(watch_keccak_inclusion))
+        .route("/v1/jobs", get(list_jobs))
+        .route("/v1/jobs/:job_id", get(get_job))
+        .route("/v1/jobs/:job_id/proof", get(get_job_proof))
+        .with_state(Gateway { service, api_keys, admin })
+        .merge(extra_routes);
+    let listener = TcpListener::bind(addr).await?;
+    match tls {
+        Some(tls) => serve_tls(listener, app, TlsAcceptor::from(tls), shutdown).await,
+        None => axum::serve(listener, app).with_graceful_shutdown(shutdown).await,
+    }
+}
+
+// axum::serve only does plaintext, so over TLS we accept and serve the connections ourselves
+async fn serve_tls(
+    listener: TcpListener,
+    app: Router,
+    acceptor: TlsAcceptor,
+    shutdown: impl Future<Output = ()>,
+) -> std::io::Result<()> {
+    let (stop, stopping) = watch::channel(false);
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                // e.g. out of file descriptors, which finishing connections may free up
+                Err(e) => {
+                    warn!("Failed to accept HTTP connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            () = &mut shutdown => break,
+        };
+        let (acceptor, app, mut stopping) = (acceptor.clone(), app.clone(), stopping.clone());
+        connections.spawn(async move {
+            // A failed handshake is the client's problem, there's nothing to serve
+            let Ok(Ok(stream)) = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await else {
+                return;
+            };
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(app));
+            tokio::pin!(connection);
+            tokio::select! {
+                _ = connection.as_mut() => return,
+                _ = stopping.wait_for(|&stop| stop) => connection.as_mut().graceful_shutdown(),
+            }
+            let _ = connection.await;
+        });
+        while connections.try_join_next().is_some() {}
+    }
+
+    // Like axum::serve, let the requests underway finish
+    stop.send_replace(true);
+    while connections.join_next().await.is_some() {}
+    Ok(())
+}
+
+// The gateway's TLS config, from the same files as the gRPC server's
+pub fn tls_config(cert: &FsPath, key: &FsPath, client_ca: Option<&FsPath>) -> Result<Arc<ServerConfig>, Box<dyn std::error::Error>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key)?))?
+        .ok_or("No private key in the TLS key file")?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in rustls_pemfile::certs(&mut BufReader::new(File::open(client_ca)?)) {
+                roots.add(cert?)?;
+            }
+            builder.with_client_cert_verifier(WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+pub struct ApiError(Status);
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        ApiError(status)
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorJson {
+    error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.0.code() {
+            Code::InvalidArgument => StatusCode::BAD_REQUEST,
+            Code::NotFound => StatusCode::NOT_FOUND,
+            Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+            Code::PermissionDenied => StatusCode::FORBIDDEN,
+            Code::FailedPrecondition => StatusCode::CONFLICT,
+            Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+            Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(ErrorJson { error: self.0.message().to_string() })).into_response()
+    }
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>, Status> {
+    hex::decode(value.strip_prefix("0x").unwrap_or(value))
+        .map_err(|e| Status::invalid_argument(format!("Invalid {}: {}", field, e)))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+// Proto enum names, case insensitive, defaulting to the enum's default value
+fn parse_enum<T: Into<i32>>(field: &str, value: Option<&str>, from_str_name: fn(&str) -> Option<T>) -> Result<i32, Status> {
+    match value {
+        None => Ok(0),
+        Some(value) => from_str_name(&value.to_uppercase())
+            .map(Into::into)
+            .ok_or_else(|| Status::invalid_argument(format!("Invalid {}: {}", field, value))),
+    }
+}
+
+#[derive(Deserialize)]
+struct InclusionPath {
+    height: u64,
+    namespace: String,
+    commitment: String,
+}
+
+#[derive(Deserialize)]
+struct InclusionQuery {
+    proof_mode: Option<String>,
+    priority: Option<String>,
+    // The service's default network if not given
+    chain_id: Option<String>,
+}
+
+fn inclusion_request(path: InclusionPath, query: InclusionQuery) -> Result<GetKeccakInclusionRequest, Status> {
+    Ok(GetKeccakInclusionRequest {
+        commitment: decode_hex("commitment", &path.commitment)?,
+        namespace: decode_hex("namespace", &path.namespace)?,
+        height: path.height,
+        proof_mode: parse_enum("proof mode", query.proof_mode.as_deref(), ProofMode::from_str_name)?,
+        job_id: String::new(),
+        priority: parse_enum("priority", query.priority.as_deref(), Priority::from_str_name)?,
+        chain_id: query.chain_id.unwrap_or_default(),
+    })
+}
+
+#[derive(Serialize)]
+struct ProofJson {
+    proof_mode: String,
+    // What an onchain verifier takes, empty for proof modes that can't be verified onchain
+    proof_bytes: String,
+    public_values: String,
+    keccak_hash: String,
+    data_root: String,
+    vkey_hash: String,
+    // The bincode encoded SP1ProofWithPublicValues, for verifying with the SP1 SDK
+    sp1_proof: String,
+}
+
+fn proof_json(details: Option<KeccakInclusionProof>, sp1_proof: &[u8]) -> Option<ProofJson> {
+    let details = details?;
+    Some(ProofJson {
+        proof_mode: ProofMode::try_from(details.proof_mode)
+            .map(|mode| mode.as_str_name().to_lowercase())
+            .unwrap_or_default(),
+        proof_bytes: encode_hex(&details.proof_bytes),
+        public_values: encode_hex(&details.public_values),
+        keccak_hash: encode_hex(&details.keccak_hash),
+        data_root: encode_hex(&details.data_root),
+        vkey_hash: details.vkey_hash,
+        sp1_proof: encode_hex(sp1_proof),
+    })
+}
+
+#[derive(Serialize)]
+struct InclusionJson {
+    status: String,
+    job_id: String,
+    queue_position: Option<u64>,
+    proof_id: Option<String>,
+    error_message: Option<String>,
+    proof: Option<ProofJson>,
+}
+
+impl From<GetKeccakInclusionResponse> for InclusionJson {
+    fn from(response: GetKeccakInclusionResponse) -> Self {
+        let (proof_id, error_message, sp1_proof) = match response.response_value {
+            Some(ResponseValue::ProofId(proof_id)) => (Some(proof_id), None, Vec::new()),
+            Some(ResponseValue::Proof(proof)) => (None, None, proof),
+            Some(ResponseValue::ErrorMessage(error)) => (None, Some(error), Vec::new()),
+            None => (None, None, Vec::new()),
+        };
+        InclusionJson {
+            status: ResponseStatus::try_from(response.status)
+                .map(|status| status.as_str_name().to_lowercase())
+                .unwrap_or_default(),
+            job_id: response.job_id,
+            queue_position: response.queue_position,
+            proof_id,
+            error_message,
+            proof: proof_json(response.proof_details, &sp1_proof),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WatchJson {
+    stage: String,
+    job_id: String,
+    proof_id: Option<String>,
+    error_message: Option<String>,
+    proof: Option<ProofJson>,
+}
+
+impl From<WatchKeccakInclusionResponse> for WatchJson {
+    fn from(response: WatchKeccakInclusionResponse) -> Self {
+        let (proof_id, error_message, sp1_proof) = match response.response_value {
+            Some(WatchResponseValue::ProofId(proof_id)) => (Some(proof_id), None, Vec::new()),
+            Some(WatchResponseValue::Proof(proof)) => (None, None, proof),
+            Some(WatchResponseValue::ErrorMessage(error)) => (None, Some(error), Vec::new()),
+            None => (None, None, Vec::new()),
+        };
+        WatchJson {
+            stage: stage_name(response.stage),
+            job_id: response.job_id,
+            proof_id,
+            error_message,
+            proof: proof_json(response.proof_details, &sp1_proof),
+        }
+    }
+}
+
+fn stage_name(stage: i32) -> String {
+    JobStage::try_from(stage)
+        .map(|stage| stage.as_str_name().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct JobJson {
+    job_id: String,
+    chain_id: String,
+    height: u64,
+    namespace: String,
+    commitment: String,
+    proof_mode: String,
+    priority: String,
+    stage: String,
+    proof_id: Option<String>,
+    error_message: Option<String>,
+    attempts: u32,
+    created_at: u64,
+    updated_at: u64,
+}
+
+impl From<JobInfo> for JobJson {
+    fn from(info: JobInfo) -> Self {
+        let request = info.request.unwrap_or_default();
+        JobJson {
+            job_id: info.job_id,
+            chain_id: request.chain_id,
+            height: request.height,
+            namespace: encode_hex(&request.namespace),
+            commitment: encode_hex(&request.commitment),
+            proof_mode: ProofMode::try_from(request.proof_mode)
+                .map(|mode| mode.as_str_name().to_lowercase())
+                .unwrap_or_default(),
+            priority: Priority::try_from(request.priority)
+                .map(|priority| priority.as_str_name().to_lowercase())
+                .unwrap_or_default(),
+            stage: stage_name(info.stage),
+            proof_id: Some(info.proof_id).filter(|proof_id| !proof_id.is_empty()),
+            error_message: Some(info.error_message).filter(|error| !error.is_empty()),
+            attempts: info.attempts,
+            created_at: info.created_at,
+            updated_at: info.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ListJobsJson {
+    jobs: Vec<JobJson>,
+    next_page_token: Option<String>,
+}
+
+impl From<ListJobsResponse> for ListJobsJson {
+    fn from(response: ListJobsResponse) -> Self {
+        ListJobsJson {
+            jobs: response.jobs.into_iter().map(JobJson::from).collect(),
+            next_page_token: Some(response.next_page_token).filter(|token| !token.is_empty()),
+        }
+    }
+}
+
+async fn get_keccak_inclusion(
+    State(gateway): State<Gateway>,
+    headers: HeaderMap,
+    Path(path): Path<InclusionPath>,
+    Query(query): Query<InclusionQuery>,
+) -> Result<Json<InclusionJson>, ApiError> {
+    let request = gateway.request(&headers, inclusion_request(path, query)?)?;
+    let response = gateway.service.get_keccak_inclusion(request).await?;
+    Ok(Json(response.into_inner().into()))
+}
+
+async fn watch_keccak_inclusion(
+    State(gateway): State<Gateway>,
+    headers: HeaderMap,
+    Path(path): Path<InclusionPath>,
+    Query(query): Query<InclusionQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let request = gateway.request(&headers, inclusion_request(path, query)?)?;
+    let updates = gateway.service.watch_keccak_inclusion(request).await?.into_inner();
+    let events = updates.map(|update| {
+        let event = match update {
+            Ok(update) => Event::default().json_data(WatchJson::from(update))
+                .unwrap_or_else(|e| Event::default().event("error").data(e.to_string())),
+            Err(status) => Event::default().event("error").data(status.message()),
+        };
+        Ok(event)
+    });
+    // Proving can take a while, keep proxies from closing the connection in the meantime
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct ListJobsQuery {
+    // Comma separated
+    stages: Option<String>,
+    min_height: Option<u64>,
+    max_height: Option<u64>,
+    namespace: Option<String>,
+    page_size: Option<u32>,
+    page_token: Option<String>,
+}
+
+async fn list_jobs(
+    State(gateway): State<Gateway>,
+    headers: HeaderMap,
+    Query(query): Query<ListJobsQuery>,
+) -> Result<Json<ListJobsJson>, ApiError> {
+    let stages = query.stages.as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|stage| !stage.is_empty())
+        .map(|stage| parse_enum("stage", Some(stage), JobStage::from_str_name))
+        .collect::<Result<_, _>>()?;
+    let request = ListJobsRequest {
+        stages,
+        min_height: query.min_height,
+        max_height: query.max_height,
+        namespace: query.namespace.map(|namespace| decode_hex("namespace", &namespace)).transpose()?.unwrap_or_default(),
+        page_size: query.page_size.unwrap_or_default(),
+        page_token: query.page_token.unwrap_or_default(),
+    };
+    let (admin, request) = gateway.admin_request(&headers, request)?;
+    let response = admin.list_jobs(request).await?;
+    Ok(Json(response.into_inner().into()))
+}
+
+async fn get_job(
+    State(gateway): State<Gateway>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobJson>, ApiError> {
+    let request = GetKeccakInclusionRequest { job_id, ..Default::default() };
+    let (admin, request) = gateway.admin_request(&headers, request)?;
+    let response = admin.get_job(request).await?;
+    Ok(Json(response.into_inner().into()))
+}
+
+async fn get_job_proof(
+    State(gateway): State<Gateway>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<Json<InclusionJson>, ApiError> {
+    let request = GetKeccakInclusionRequest { job_id, ..Default::default() };
+    let response = gateway.service.get_keccak_inclusion(gateway.request(&headers, request)?).await?;
+    Ok(Json(response.into_inner().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::{pki_types::ServerName, ClientConfig};
+    use tokio_rustls::TlsConnector;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        FsPath::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    #[tokio::test]
+    async fn serves_over_tls() {
+        let tls = tls_config(&fixture("localhost.pem"), &fixture("localhost.key"), None).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/", get(|| async { "hello" }));
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_tls(listener, app, TlsAcceptor::from(tls), async { let _ = stopped.await; }));
+
+        let mut roots = RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut BufReader::new(File::open(fixture("localhost.pem")).unwrap())) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let client = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut stream = TlsConnector::from(Arc::new(client))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("hello"), "{}", response);
+
+        // Plaintext clients don't get anywhere
+        let mut plain = tokio::net::TcpStream::connect(addr).await.unwrap();
+        plain.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = Vec::new();
+        let _ = plain.read_to_end(&mut response).await;
+        assert!(!String::from_utf8_lossy(&response).contains("hello"));
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
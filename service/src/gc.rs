@@ -0,0 +1,222 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::error;
+
+use crate::job::{unix_now, JobRecord, JobStatus};
+use crate::storage::{JobStore, StorageError};
+
+// Which completed proofs to keep. Pruned jobs keep their record as a tombstone with
+// status Pruned, so a later request for the same job is recognized and re-proved.
+// Their zkVM input is kept, so a re-proved job doesn't need to fetch it again.
+#[derive(Clone, Debug, Default)]
+pub struct RetentionPolicy {
+    // Prune proofs completed longer ago than this
+    pub max_age: Option<Duration>,
+    // Prune the oldest proofs until the total encoded size of the rest fits
+    pub max_total_size: Option<u64>,
+    // Only keep this many of the most recently completed proofs for each namespace
+    pub keep_per_namespace: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.max_age.is_none() && self.max_total_size.is_none() && self.keep_per_namespace.is_none()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub pruned_proofs: u64,
+    pub freed_bytes: u64,
+}
+
+struct Candidate {
+    key: Vec<u8>,
+    namespace: Vec<u8>,
+    record: JobRecord,
+    size: u64,
+}
+
+pub fn collect_garbage(
+    storage: &dyn JobStore,
+    policy: &RetentionPolicy,
+    job_updates: &broadcast::Sender<Vec<u8>>,
+) -> Result<GcReport, StorageError> {
+    let mut report = GcReport::default();
+    if policy.is_empty() {
+        return Ok(report);
+    }
+
+    let mut completed = Vec::new();
+    for entry in storage.jobs_from(std::ops::Bound::Unbounded) {
+        let (key, job, record) = entry?;
+        if !matches!(record.status, JobStatus::Completed) {
+            continue;
+        }
+        let size = storage.proof_size(&key)?.unwrap_or_default();
+        completed.push(Candidate { key, namespace: job.namespace, record, size });
+    }
+    // Newest first, so whatever is over a limit is always the oldest
+    completed.sort_by_key(|candidate| Reverse(candidate.record.updated_at));
+
+    let cutoff = policy.max_age.map(|max_age| unix_now().saturating_sub(max_age.as_secs()));
+    let mut kept_per_namespace: HashMap<&[u8], usize> = HashMap::new();
+    let mut kept_size = 0;
+    let mut prune = Vec::new();
+    for candidate in &completed {
+        let kept = kept_per_namespace.entry(candidate.namespace.as_slice()).or_default();
+        let expired = cutoff.is_some_and(|cutoff| candidate.record.updated_at < cutoff);
+        let over_count = policy.keep_per_namespace.is_some_and(|keep| *kept >= keep);
+        let over_size = policy.max_total_size.is_some_and(|max| kept_size + candidate.size > max);
+
+        if expired || over_count || over_size {
+            prune.push(candidate);
+        } else {
+            *kept += 1;
+            kept_size += candidate.size;
+        }
+    }
+
+    for candidate in prune {
+        let mut pruned = candidate.record.clone();
+        pruned.update(JobStatus::Pruned);
+        // Skip the job if it changed since we looked at it, e.g. it was retried.
+        // Mark it pruned before removing the proof, so a completed job always has one.
+        if !storage.replace_job(&candidate.key, &candidate.record, &pruned)? {
+            continue;
+        }
+        storage.remove_proof(&candidate.key)?;
+        let _ = job_updates.send(candidate.key.clone());
+        report.pruned_proofs += 1;
+        report.freed_bytes += candidate.size;
+    }
+    Ok(report)
+}
+
+// Applies the retention policy every `interval`
+pub async fn run_gc(
+    storage: Arc<dyn JobStore>,
+    policy: RetentionPolicy,
+    interval: Duration,
+    job_updates: broadcast::Sender<Vec<u8>>,
+) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        // Goes through every job, so keep it off the async runtime
+        let (storage, policy, job_updates) = (storage.clone(), policy.clone(), job_updates.clone());
+        let result = tokio::task::spawn_blocking(move || collect_garbage(storage.as_ref(), &policy, &job_updates)).await;
+        match result {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!("Failed to prune proofs: {}", e),
+            Err(e) => error!("Garbage collection panicked: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eqs::Priority;
+    use crate::storage::{job_key, MemoryStore};
+    use crate::test_utils;
+
+    // A completed job with a proof and input, finished `age` seconds ago
+    fn completed(store: &MemoryStore, height: u64, age: u64) -> Vec<u8> {
+        let key = job_key(&test_utils::job(height));
+        let mut record = JobRecord::queued(&key, Priority::Normal);
+        record.update(JobStatus::Completed);
+        record.updated_at -= age;
+        store.put_job(&key, &record).unwrap();
+        store.put_proof(&key, &test_utils::proof()).unwrap();
+        store.put_input(&key, &test_utils::input()).unwrap();
+        key
+    }
+
+    fn is_pruned(store: &MemoryStore, key: &[u8]) -> bool {
+        let pruned = matches!(store.get_job(key).unwrap().unwrap().status, JobStatus::Pruned);
+        // Only pruned jobs lose their proof
+        assert_eq!(store.get_proof(key).unwrap().is_none(), pruned);
+        pruned
+    }
+
+    #[test]
+    fn keeps_everything_without_a_policy() {
+        let store = MemoryStore::new();
+        let key = completed(&store, 1, 1_000_000);
+        let report = collect_garbage(&store, &RetentionPolicy::default(), &broadcast::channel(16).0).unwrap();
+        assert_eq!(report.pruned_proofs, 0);
+        assert!(!is_pruned(&store, &key));
+    }
+
+    #[test]
+    fn prunes_expired_proofs() {
+        let store = MemoryStore::new();
+        let old = completed(&store, 1, 7200);
+        let new = completed(&store, 2, 60);
+        let (job_updates, mut updates) = broadcast::channel(16);
+        let policy = RetentionPolicy { max_age: Some(Duration::from_secs(3600)), ..Default::default() };
+
+        let report = collect_garbage(&store, &policy, &job_updates).unwrap();
+        assert_eq!(report.pruned_proofs, 1);
+        assert_eq!(report.freed_bytes, bincode::serialize(&test_utils::proof()).unwrap().len() as u64);
+        assert!(is_pruned(&store, &old));
+        assert!(!is_pruned(&store, &new));
+        assert_eq!(updates.try_recv().unwrap(), old);
+    }
+
+    #[test]
+    fn keeps_the_input_of_pruned_jobs() {
+        let store = MemoryStore::new();
+        let key = completed(&store, 1, 7200);
+        let policy = RetentionPolicy { max_age: Some(Duration::from_secs(3600)), ..Default::default() };
+
+        collect_garbage(&store, &policy, &broadcast::channel(16).0).unwrap();
+        assert!(is_pruned(&store, &key));
+        assert_eq!(store.get_input(&key).unwrap().unwrap().blob_data, test_utils::input().blob_data);
+    }
+
+    #[test]
+    fn keeps_the_newest_proofs_per_namespace() {
+        let store = MemoryStore::new();
+        let keys: Vec<_> = (0..3).map(|height| completed(&store, height, 100 - height)).collect();
+        let policy = RetentionPolicy { keep_per_namespace: Some(2), ..Default::default() };
+
+        collect_garbage(&store, &policy, &broadcast::channel(16).0).unwrap();
+        assert!(is_pruned(&store, &keys[0]));
+        assert!(!is_pruned(&store, &keys[1]));
+        assert!(!is_pruned(&store, &keys[2]));
+    }
+
+    #[test]
+    fn prunes_the_oldest_proofs_over_the_size_limit() {
+        let store = MemoryStore::new();
+        let keys: Vec<_> = (0..3).map(|height| completed(&store, height, 100 - height)).collect();
+        let size = store.proof_size(&keys[0]).unwrap().unwrap();
+        let policy = RetentionPolicy { max_total_size: Some(size * 2), ..Default::default() };
+
+        collect_garbage(&store, &policy, &broadcast::channel(16).0).unwrap();
+        assert!(is_pruned(&store, &keys[0]));
+        assert!(!is_pruned(&store, &keys[1]));
+        assert!(!is_pruned(&store, &keys[2]));
+    }
+
+    #[test]
+    fn leaves_unfinished_jobs_alone() {
+        let store = MemoryStore::new();
+        let key = job_key(&test_utils::job(1));
+        let mut record = JobRecord::queued(&key, Priority::Normal);
+        record.update(JobStatus::Proving("request-1".to_string()));
+        record.updated_at -= 7200;
+        store.put_job(&key, &record).unwrap();
+        store.put_input(&key, &test_utils::input()).unwrap();
+        let policy = RetentionPolicy { max_age: Some(Duration::from_secs(3600)), ..Default::default() };
+
+        let report = collect_garbage(&store, &policy, &broadcast::channel(16).0).unwrap();
+        assert_eq!(report.pruned_proofs, 0);
+        assert!(store.get_input(&key).unwrap().is_some());
+    }
+}
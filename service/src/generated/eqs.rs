@@ -10,11 +10,30 @@ pub struct GetKeccakInclusionRequest {
     /// block height
     #[prost(uint64, tag = "3")]
     pub height: u64,
+    #[prost(enumeration = "ProofMode", tag = "4")]
+    pub proof_mode: i32,
+    /// Look up an existing job by ID instead, the other fields are ignored when set
+    #[prost(string, tag = "5")]
+    pub job_id: ::prost::alloc::string::String,
+    /// Raises the priority of a queued job if it's higher than the job's current one
+    #[prost(enumeration = "Priority", tag = "6")]
+    pub priority: i32,
+    /// Celestia network, e.g. celestia, mocha-4 or arabica-11. The service's default network if empty
+    #[prost(string, tag = "7")]
+    pub chain_id: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetKeccakInclusionResponse {
     #[prost(enumeration = "get_keccak_inclusion_response::Status", tag = "1")]
     pub status: i32,
+    /// Used when status is COMPLETE
+    #[prost(message, optional, tag = "5")]
+    pub proof_details: ::core::option::Option<KeccakInclusionProof>,
+    #[prost(string, tag = "6")]
+    pub job_id: ::prost::alloc::string::String,
+    /// How many jobs will be started before this one, while it's queued
+    #[prost(uint64, optional, tag = "7")]
+    pub queue_position: ::core::option::Option<u64>,
     #[prost(oneof = "get_keccak_inclusion_response::ResponseValue", tags = "2, 3, 4")]
     pub response_value: ::core::option::Option<
         get_keccak_inclusion_response::ResponseValue,
@@ -74,6 +93,232 @@ pub mod get_keccak_inclusion_response {
         ErrorMessage(::prost::alloc::string::String),
     }
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeccakInclusionProof {
+    /// Proof bytes as expected by the SP1 Solidity verifier
+    #[prost(bytes = "vec", tag = "1")]
+    pub proof_bytes: ::prost::alloc::vec::Vec<u8>,
+    /// ABI encoded KeccakInclusionToDataRootProofOutput
+    #[prost(bytes = "vec", tag = "2")]
+    pub public_values: ::prost::alloc::vec::Vec<u8>,
+    /// 32 byte keccak hash of the blob data
+    #[prost(bytes = "vec", tag = "3")]
+    pub keccak_hash: ::prost::alloc::vec::Vec<u8>,
+    /// 32 byte data root of the block
+    #[prost(bytes = "vec", tag = "4")]
+    pub data_root: ::prost::alloc::vec::Vec<u8>,
+    /// bytes32 hash of the program verification key
+    #[prost(string, tag = "5")]
+    pub vkey_hash: ::prost::alloc::string::String,
+    #[prost(enumeration = "ProofMode", tag = "6")]
+    pub proof_mode: i32,
+}
+/// Sent once for the current stage of the job and again on every transition,
+/// the stream closes once the job is COMPLETE, FAILED or PRUNED
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchKeccakInclusionResponse {
+    #[prost(enumeration = "JobStage", tag = "1")]
+    pub stage: i32,
+    /// Used when stage is COMPLETE
+    #[prost(message, optional, tag = "5")]
+    pub proof_details: ::core::option::Option<KeccakInclusionProof>,
+    #[prost(string, tag = "6")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(oneof = "watch_keccak_inclusion_response::ResponseValue", tags = "2, 3, 4")]
+    pub response_value: ::core::option::Option<
+        watch_keccak_inclusion_response::ResponseValue,
+    >,
+}
+/// Nested message and enum types in `WatchKeccakInclusionResponse`.
+pub mod watch_keccak_inclusion_response {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum ResponseValue {
+        /// Used when stage is PROVING
+        #[prost(string, tag = "2")]
+        ProofId(::prost::alloc::string::String),
+        /// Used when stage is COMPLETE
+        #[prost(bytes, tag = "3")]
+        Proof(::prost::alloc::vec::Vec<u8>),
+        /// Used when stage is FAILED
+        #[prost(string, tag = "4")]
+        ErrorMessage(::prost::alloc::string::String),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobInfo {
+    #[prost(message, optional, tag = "1")]
+    pub request: ::core::option::Option<GetKeccakInclusionRequest>,
+    #[prost(enumeration = "JobStage", tag = "2")]
+    pub stage: i32,
+    /// Succinct Network request ID, once the job has reached PROVING
+    #[prost(string, tag = "3")]
+    pub proof_id: ::prost::alloc::string::String,
+    /// Unix timestamp in seconds
+    #[prost(uint64, tag = "4")]
+    pub created_at: u64,
+    /// Unix timestamp in seconds
+    #[prost(uint64, tag = "5")]
+    pub updated_at: u64,
+    /// Number of times the job has started fetching
+    #[prost(uint32, tag = "6")]
+    pub attempts: u32,
+    /// Set when stage is FAILED
+    #[prost(string, tag = "7")]
+    pub error_message: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub job_id: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListJobsRequest {
+    /// Only jobs in one of these stages, all stages if empty
+    #[prost(enumeration = "JobStage", repeated, tag = "1")]
+    pub stages: ::prost::alloc::vec::Vec<i32>,
+    /// Inclusive
+    #[prost(uint64, optional, tag = "2")]
+    pub min_height: ::core::option::Option<u64>,
+    /// Inclusive
+    #[prost(uint64, optional, tag = "3")]
+    pub max_height: ::core::option::Option<u64>,
+    /// Only jobs for this namespace, all namespaces if empty
+    #[prost(bytes = "vec", tag = "4")]
+    pub namespace: ::prost::alloc::vec::Vec<u8>,
+    /// Defaults to 100, at most 1000
+    #[prost(uint32, tag = "5")]
+    pub page_size: u32,
+    /// next_page_token from the previous response
+    #[prost(string, tag = "6")]
+    pub page_token: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListJobsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub jobs: ::prost::alloc::vec::Vec<JobInfo>,
+    /// Empty when there are no more jobs
+    #[prost(string, tag = "2")]
+    pub next_page_token: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobInput {
+    /// KeccakInclusionToDataRootProofInput in the JSON format runner-keccak-inclusion reads
+    #[prost(string, tag = "1")]
+    pub input_json: ::prost::alloc::string::String,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CompactRequest {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CompactResponse {
+    #[prost(uint64, tag = "1")]
+    pub pruned_proofs: u64,
+    /// Encoded size of the pruned proofs
+    #[prost(uint64, tag = "2")]
+    pub freed_bytes: u64,
+    /// Size of the DB after pruning
+    #[prost(uint64, tag = "3")]
+    pub size_on_disk: u64,
+}
+/// HIGH jobs are always started before NORMAL jobs, which are always started before LOW jobs
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Priority {
+    Normal = 0,
+    High = 1,
+    Low = 2,
+}
+impl Priority {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Normal => "NORMAL",
+            Self::High => "HIGH",
+            Self::Low => "LOW",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "NORMAL" => Some(Self::Normal),
+            "HIGH" => Some(Self::High),
+            "LOW" => Some(Self::Low),
+            _ => None,
+        }
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ProofMode {
+    Groth16 = 0,
+    Plonk = 1,
+    Compressed = 2,
+    Core = 3,
+}
+impl ProofMode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Groth16 => "GROTH16",
+            Self::Plonk => "PLONK",
+            Self::Compressed => "COMPRESSED",
+            Self::Core => "CORE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GROTH16" => Some(Self::Groth16),
+            "PLONK" => Some(Self::Plonk),
+            "COMPRESSED" => Some(Self::Compressed),
+            "CORE" => Some(Self::Core),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum JobStage {
+    Queued = 0,
+    Fetching = 1,
+    Proving = 2,
+    Complete = 3,
+    Failed = 4,
+    /// The proof was removed by the retention policy, requesting it again will re-prove it
+    Pruned = 5,
+}
+impl JobStage {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Queued => "QUEUED",
+            Self::Fetching => "FETCHING",
+            Self::Proving => "PROVING",
+            Self::Complete => "COMPLETE",
+            Self::Failed => "FAILED",
+            Self::Pruned => "PRUNED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "QUEUED" => Some(Self::Queued),
+            "FETCHING" => Some(Self::Fetching),
+            "PROVING" => Some(Self::Proving),
+            "COMPLETE" => Some(Self::Complete),
+            "FAILED" => Some(Self::Failed),
+            "PRUNED" => Some(Self::Pruned),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod inclusion_client {
     #![allow(
@@ -189,6 +434,245 @@ pub mod inclusion_client {
                 .insert(GrpcMethod::new("eqs.Inclusion", "GetKeccakInclusion"));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn watch_keccak_inclusion(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<
+                tonic::codec::Streaming<super::WatchKeccakInclusionResponse>,
+            >,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/eqs.Inclusion/WatchKeccakInclusion",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("eqs.Inclusion", "WatchKeccakInclusion"));
+            self.inner.server_streaming(req, path, codec).await
+        }
+    }
+}
+/// Generated client implementations.
+pub mod admin_client {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// Operator RPCs, the admin token must be sent in the x-admin-token metadata
+    #[derive(Debug, Clone)]
+    pub struct AdminClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl AdminClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> AdminClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> AdminClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + std::marker::Send + std::marker::Sync,
+        {
+            AdminClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
+        }
+        /// Every client's jobs, so only for operators
+        pub async fn list_jobs(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListJobsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListJobsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/eqs.Admin/ListJobs");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("eqs.Admin", "ListJobs"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_job(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<tonic::Response<super::JobInfo>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/eqs.Admin/GetJob");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("eqs.Admin", "GetJob"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Stops working on the job and marks it FAILED
+        pub async fn cancel_job(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<tonic::Response<super::JobInfo>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/eqs.Admin/CancelJob");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("eqs.Admin", "CancelJob"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Requeues a COMPLETE or FAILED job from scratch
+        pub async fn retry_job(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<tonic::Response<super::JobInfo>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/eqs.Admin/RetryJob");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("eqs.Admin", "RetryJob"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// The zkVM input the job was proved with, once it has been fetched
+        pub async fn get_job_input(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<tonic::Response<super::JobInput>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/eqs.Admin/GetJobInput");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("eqs.Admin", "GetJobInput"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Prunes proofs according to the retention policy right away and flushes the DB. It doesn't compact the DB files, sled reuses the freed space on its own.
+        pub async fn compact(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CompactRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CompactResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/eqs.Admin/Compact");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("eqs.Admin", "Compact"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -211,6 +695,22 @@ pub mod inclusion_server {
             tonic::Response<super::GetKeccakInclusionResponse>,
             tonic::Status,
         >;
+        /// Server streaming response type for the WatchKeccakInclusion method.
+        type WatchKeccakInclusionStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<
+                    super::WatchKeccakInclusionResponse,
+                    tonic::Status,
+                >,
+            >
+            + std::marker::Send
+            + 'static;
+        async fn watch_keccak_inclusion(
+            &self,
+            request: tonic::Request<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::WatchKeccakInclusionStream>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
     pub struct InclusionServer<T> {
@@ -334,14 +834,62 @@ pub mod inclusion_server {
                     };
                     Box::pin(fut)
                 }
-                _ => {
-                    Box::pin(async move {
-                        let mut response = http::Response::new(empty_body());
-                        let headers = response.headers_mut();
-                        headers
-                            .insert(
-                                tonic::Status::GRPC_STATUS,
-                                (tonic::Code::Unimplemented as i32).into(),
+                "/eqs.Inclusion/WatchKeccakInclusion" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchKeccakInclusionSvc<T: Inclusion>(pub Arc<T>);
+                    impl<
+                        T: Inclusion,
+                    > tonic::server::ServerStreamingService<
+                        super::GetKeccakInclusionRequest,
+                    > for WatchKeccakInclusionSvc<T> {
+                        type Response = super::WatchKeccakInclusionResponse;
+                        type ResponseStream = T::WatchKeccakInclusionStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetKeccakInclusionRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Inclusion>::watch_keccak_inclusion(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = WatchKeccakInclusionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        let mut response = http::Response::new(empty_body());
+                        let headers = response.headers_mut();
+                        headers
+                            .insert(
+                                tonic::Status::GRPC_STATUS,
+                                (tonic::Code::Unimplemented as i32).into(),
                             );
                         headers
                             .insert(
@@ -372,3 +920,430 @@ pub mod inclusion_server {
         const NAME: &'static str = SERVICE_NAME;
     }
 }
+/// Generated server implementations.
+pub mod admin_server {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with AdminServer.
+    #[async_trait]
+    pub trait Admin: std::marker::Send + std::marker::Sync + 'static {
+        /// Every client's jobs, so only for operators
+        async fn list_jobs(
+            &self,
+            request: tonic::Request<super::ListJobsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListJobsResponse>,
+            tonic::Status,
+        >;
+        async fn get_job(
+            &self,
+            request: tonic::Request<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<tonic::Response<super::JobInfo>, tonic::Status>;
+        /// Stops working on the job and marks it FAILED
+        async fn cancel_job(
+            &self,
+            request: tonic::Request<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<tonic::Response<super::JobInfo>, tonic::Status>;
+        /// Requeues a COMPLETE or FAILED job from scratch
+        async fn retry_job(
+            &self,
+            request: tonic::Request<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<tonic::Response<super::JobInfo>, tonic::Status>;
+        /// The zkVM input the job was proved with, once it has been fetched
+        async fn get_job_input(
+            &self,
+            request: tonic::Request<super::GetKeccakInclusionRequest>,
+        ) -> std::result::Result<tonic::Response<super::JobInput>, tonic::Status>;
+        /// Prunes proofs according to the retention policy right away and flushes the DB. It doesn't compact the DB files, sled reuses the freed space on its own.
+        async fn compact(
+            &self,
+            request: tonic::Request<super::CompactRequest>,
+        ) -> std::result::Result<tonic::Response<super::CompactResponse>, tonic::Status>;
+    }
+    /// Operator RPCs, the admin token must be sent in the x-admin-token metadata
+    #[derive(Debug)]
+    pub struct AdminServer<T> {
+        inner: Arc<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    impl<T> AdminServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for AdminServer<T>
+    where
+        T: Admin,
+        B: Body + std::marker::Send + 'static,
+        B::Error: Into<StdError> + std::marker::Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            match req.uri().path() {
+                "/eqs.Admin/ListJobs" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListJobsSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::ListJobsRequest>
+                    for ListJobsSvc<T> {
+                        type Response = super::ListJobsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListJobsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Admin>::list_jobs(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ListJobsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/eqs.Admin/GetJob" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetJobSvc<T: Admin>(pub Arc<T>);
+                    impl<
+                        T: Admin,
+                    > tonic::server::UnaryService<super::GetKeccakInclusionRequest>
+                    for GetJobSvc<T> {
+                        type Response = super::JobInfo;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetKeccakInclusionRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Admin>::get_job(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetJobSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/eqs.Admin/CancelJob" => {
+                    #[allow(non_camel_case_types)]
+                    struct CancelJobSvc<T: Admin>(pub Arc<T>);
+                    impl<
+                        T: Admin,
+                    > tonic::server::UnaryService<super::GetKeccakInclusionRequest>
+                    for CancelJobSvc<T> {
+                        type Response = super::JobInfo;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetKeccakInclusionRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Admin>::cancel_job(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CancelJobSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/eqs.Admin/RetryJob" => {
+                    #[allow(non_camel_case_types)]
+                    struct RetryJobSvc<T: Admin>(pub Arc<T>);
+                    impl<
+                        T: Admin,
+                    > tonic::server::UnaryService<super::GetKeccakInclusionRequest>
+                    for RetryJobSvc<T> {
+                        type Response = super::JobInfo;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetKeccakInclusionRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Admin>::retry_job(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RetryJobSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/eqs.Admin/GetJobInput" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetJobInputSvc<T: Admin>(pub Arc<T>);
+                    impl<
+                        T: Admin,
+                    > tonic::server::UnaryService<super::GetKeccakInclusionRequest>
+                    for GetJobInputSvc<T> {
+                        type Response = super::JobInput;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetKeccakInclusionRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Admin>::get_job_input(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetJobInputSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/eqs.Admin/Compact" => {
+                    #[allow(non_camel_case_types)]
+                    struct CompactSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::CompactRequest>
+                    for CompactSvc<T> {
+                        type Response = super::CompactResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CompactRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Admin>::compact(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CompactSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        let mut response = http::Response::new(empty_body());
+                        let headers = response.headers_mut();
+                        headers
+                            .insert(
+                                tonic::Status::GRPC_STATUS,
+                                (tonic::Code::Unimplemented as i32).into(),
+                            );
+                        headers
+                            .insert(
+                                http::header::CONTENT_TYPE,
+                                tonic::metadata::GRPC_CONTENT_TYPE,
+                            );
+                        Ok(response)
+                    })
+                }
+            }
+        }
+    }
+    impl<T> Clone for AdminServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+                max_decoding_message_size: self.max_decoding_message_size,
+                max_encoding_message_size: self.max_encoding_message_size,
+            }
+        }
+    }
+    /// Generated gRPC service name
+    pub const SERVICE_NAME: &str = "eqs.Admin";
+    impl<T> tonic::server::NamedService for AdminServer<T> {
+        const NAME: &'static str = SERVICE_NAME;
+    }
+}
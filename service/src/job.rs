@@ -0,0 +1,261 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use tonic::Status;
+use celestia_types::nmt::Namespace;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::eqs::{GetKeccakInclusionRequest, JobInfo, JobStage, Priority, ProofMode};
+use crate::node::Networks;
+use crate::storage::{self, JobStore};
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub height: u64,
+    pub namespace: Vec<u8>,
+    pub commitment: Vec<u8>,
+    pub proof_mode: ProofMode,
+    // The Celestia network the blob is on
+    pub chain_id: String,
+}
+
+impl Job {
+    pub fn from_request(request: &GetKeccakInclusionRequest, default_chain_id: &str) -> Result<Self, Status> {
+        if request.commitment.len() != 32 {
+            return Err(Status::invalid_argument("Invalid commitment"));
+        }
+        Namespace::from_raw(&request.namespace)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let proof_mode = ProofMode::try_from(request.proof_mode)
+            .map_err(|_| Status::invalid_argument("Invalid proof mode"))?;
+        Ok(Job {
+            height: request.height,
+            namespace: request.namespace.clone(),
+            commitment: request.commitment.clone(),
+            proof_mode,
+            chain_id: if request.chain_id.is_empty() {
+                default_chain_id.to_string()
+            } else {
+                request.chain_id.clone()
+            },
+        })
+    }
+}
+
+// Priority isn't part of the job, the same job requested with different priorities is still one job
+pub fn request_priority(request: &GetKeccakInclusionRequest) -> Result<Priority, Status> {
+    Priority::try_from(request.priority)
+        .map_err(|_| Status::invalid_argument("Invalid priority"))
+}
+
+// Derived from the job key, so resubmitting the same request always gives back the same ID.
+// It's stored with the job, so it stays the same even if the key layout changes.
+pub fn job_id(key: &[u8]) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, key).to_string()
+}
+
+// Finds the job a request refers to, either by job ID or by its parameters
+pub fn resolve_job(store: &dyn JobStore, request: &GetKeccakInclusionRequest, networks: &Networks) -> Result<(Job, Vec<u8>), Status> {
+    let (job, key) = if request.job_id.is_empty() {
+        let job = Job::from_request(request, networks.default_chain_id())?;
+        let key = storage::job_key(&job);
+        (job, key)
+    } else {
+        let key = store.job_key_by_id(&request.job_id)?
+            .ok_or_else(|| Status::not_found("Job not found"))?;
+        (storage::decode_job_key(&key)?, key)
+    };
+
+    if networks.node(&job.chain_id).is_none() {
+        return Err(Status::invalid_argument(format!("Unknown chain ID {}", job.chain_id)));
+    }
+    Ok((job, key))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum JobStatus {
+    // Accepted, waiting for the worker to pick it up
+    Queued,
+    // Fetching the blob, header and proofs from the Celestia node
+    Fetching,
+    // The Succinct Network job ID
+    Proving(String),
+    // The proof itself is stored separately, see JobStore::get_proof
+    Completed,
+    Failed(String),
+    // The proof was removed by the retention policy, the job is requeued if it's requested again
+    Pruned,
+}
+
+impl JobStatus {
+    pub fn stage(&self) -> JobStage {
+        match self {
+            JobStatus::Queued => JobStage::Queued,
+            JobStatus::Fetching => JobStage::Fetching,
+            JobStatus::Proving(_) => JobStage::Proving,
+            JobStatus::Completed => JobStage::Complete,
+            JobStatus::Failed(_) => JobStage::Failed,
+            JobStatus::Pruned => JobStage::Pruned,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed(_) | JobStatus::Pruned)
+    }
+}
+
+// What we store in the DB for each job
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobRecord {
+    pub job_id: String,
+    pub status: JobStatus,
+    // The highest priority the job has been requested with
+    pub priority: Priority,
+    // The Succinct Network job ID, kept after the job has finished proving
+    pub proof_id: Option<String>,
+    // How many times the worker has started fetching for this job
+    pub attempts: u32,
+    // Unix timestamps in seconds
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl JobRecord {
+    pub fn queued(key: &[u8], priority: Priority) -> Self {
+        let now = unix_now();
+        JobRecord {
+            job_id: job_id(key),
+            status: JobStatus::Queued,
+            priority,
+            proof_id: None,
+            attempts: 0,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn update(&mut self, status: JobStatus) {
+        match &status {
+            // Requeued, so the old prover request no longer applies
+            JobStatus::Queued => self.proof_id = None,
+            JobStatus::Fetching => self.attempts += 1,
+            JobStatus::Proving(proof_id) => self.proof_id = Some(proof_id.clone()),
+            _ => {}
+        }
+        self.status = status;
+        self.updated_at = unix_now();
+    }
+
+    pub fn info(&self, job: &Job) -> JobInfo {
+        JobInfo {
+            job_id: self.job_id.clone(),
+            request: Some(GetKeccakInclusionRequest {
+                commitment: job.commitment.clone(),
+                namespace: job.namespace.clone(),
+                height: job.height,
+                proof_mode: job.proof_mode as i32,
+                job_id: String::new(),
+                priority: self.priority as i32,
+                chain_id: job.chain_id.clone(),
+            }),
+            stage: self.status.stage() as i32,
+            proof_id: self.proof_id.clone().unwrap_or_default(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            attempts: self.attempts,
+            error_message: match &self.status {
+                JobStatus::Failed(error) => error.clone(),
+                _ => String::new(),
+            },
+        }
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{self, CHAIN_ID};
+
+    fn request() -> GetKeccakInclusionRequest {
+        let job = test_utils::job(10);
+        GetKeccakInclusionRequest {
+            commitment: job.commitment,
+            namespace: job.namespace,
+            height: job.height,
+            proof_mode: ProofMode::Groth16 as i32,
+            job_id: String::new(),
+            priority: Priority::Normal as i32,
+            chain_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn from_request_defaults_chain_id() {
+        let job = Job::from_request(&request(), CHAIN_ID).unwrap();
+        assert_eq!(job.chain_id, CHAIN_ID);
+
+        let other = Job::from_request(&GetKeccakInclusionRequest { chain_id: "other-1".to_string(), ..request() }, CHAIN_ID).unwrap();
+        assert_eq!(other.chain_id, "other-1");
+    }
+
+    #[test]
+    fn from_request_rejects_invalid_requests() {
+        let short_commitment = GetKeccakInclusionRequest { commitment: vec![7; 31], ..request() };
+        let bad_namespace = GetKeccakInclusionRequest { namespace: vec![1, 2], ..request() };
+        let bad_mode = GetKeccakInclusionRequest { proof_mode: 99, ..request() };
+        for request in [short_commitment, bad_namespace, bad_mode] {
+            let status = Job::from_request(&request, CHAIN_ID).unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn job_id_is_stable() {
+        let key = storage::job_key(&test_utils::job(10));
+        assert_eq!(job_id(&key), job_id(&key));
+        assert_ne!(job_id(&key), job_id(&storage::job_key(&test_utils::job(11))));
+        assert_eq!(JobRecord::queued(&key, Priority::Low).job_id, job_id(&key));
+    }
+
+    #[test]
+    fn record_transitions() {
+        let mut record = JobRecord::queued(b"key", Priority::Normal);
+        assert!(matches!(record.status, JobStatus::Queued));
+        assert_eq!(record.attempts, 0);
+
+        record.update(JobStatus::Fetching);
+        assert_eq!(record.attempts, 1);
+        assert!(!record.status.is_terminal());
+
+        record.update(JobStatus::Proving("request-1".to_string()));
+        assert_eq!(record.proof_id.as_deref(), Some("request-1"));
+        assert_eq!(record.status.stage(), JobStage::Proving);
+
+        // The prover request is kept once the proof is in
+        record.update(JobStatus::Completed);
+        assert_eq!(record.proof_id.as_deref(), Some("request-1"));
+        assert!(record.status.is_terminal());
+
+        record.update(JobStatus::Pruned);
+        assert!(record.status.is_terminal());
+
+        // Requeuing starts over with a new prover request, attempts keep counting
+        record.update(JobStatus::Queued);
+        assert_eq!(record.proof_id, None);
+        record.update(JobStatus::Fetching);
+        assert_eq!(record.attempts, 2);
+
+        record.update(JobStatus::Failed("oops".to_string()));
+        assert!(record.status.is_terminal());
+        let info = record.info(&test_utils::job(10));
+        assert_eq!(info.stage, JobStage::Failed as i32);
+        assert_eq!(info.error_message, "oops");
+        assert_eq!(info.attempts, 2);
+    }
+}
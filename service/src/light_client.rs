@@ -0,0 +1,492 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::{info, warn};
+
+use celestia_types::{Error as HeaderError, ExtendedHeader, VerificationError};
+use tendermint::Hash;
+
+use crate::job::unix_now;
+use crate::node::{CelestiaNode, NodeError};
+use crate::storage::JobStore;
+
+// How many headers to fetch at once when following hash links back from a verified header
+const BACKWARD_BATCH: u64 = 256;
+
+// A header we trust without verifying it, given by the operator or verified before a restart
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Checkpoint {
+    pub height: u64,
+    pub hash: Hash,
+}
+
+/*
+    Verifies headers from the node before we prove anything with them, so a compromised node
+    can't get us to prove inclusion under a data root that was never on the chain.
+
+    Everything starts from the checkpoint, or from the newest header verified before a restart
+    if that's newer, so a restart doesn't take us back to a checkpoint that may have fallen out
+    of the trusting period since. Headers after the newest one we've verified are
+    checked the way a Tendermint light client does it: skip straight to the header if more than
+    1/3 of the trusted validator set signed its commit, otherwise verify the header halfway
+    there first. That's only safe while the trusted header is within the trusting period, so
+    the newest verified header is kept moving along with the node's head.
+    Headers before it are checked by following the last_block_id hash links back from a
+    verified header, which holds no matter how old they are.
+*/
+pub struct LightClient {
+    chain_id: String,
+    checkpoint: Checkpoint,
+    trusting_period: Duration,
+    // Where the newest verified header is kept
+    storage: Arc<dyn JobStore>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    // The newest header we've verified, None until the checkpoint has been fetched
+    latest: Option<ExtendedHeader>,
+    // Hashes of verified headers by height, to follow hash links back from. Every header
+    // verified going forward is kept, going back only one per batch is.
+    hashes: BTreeMap<u64, Hash>,
+}
+
+impl LightClient {
+    pub fn new(chain_id: String, checkpoint: Checkpoint, trusting_period: Duration, storage: Arc<dyn JobStore>) -> Self {
+        LightClient {
+            chain_id,
+            checkpoint,
+            trusting_period,
+            storage,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(height = header.height().value()))]
+    pub async fn verify(&self, client: &dyn CelestiaNode, header: &ExtendedHeader) -> Result<(), NodeError> {
+        let state = self.state.lock().await;
+        self.verify_locked(state, client, header).await
+    }
+
+    // Moves the newest verified header up to the node's head. Skipped if a verification is
+    // already underway, following hash links back can take a while.
+    pub async fn sync_to(&self, client: &dyn CelestiaNode, head: &ExtendedHeader) -> Result<(), NodeError> {
+        match self.state.try_lock() {
+            Ok(state) => self.verify_locked(state, client, head).await,
+            Err(_) => Ok(()),
+        }
+    }
+
+    async fn verify_locked(&self, mut state: MutexGuard<'_, State>, client: &dyn CelestiaNode, header: &ExtendedHeader) -> Result<(), NodeError> {
+        header.validate().map_err(|e| NodeError::Failed(format!("Invalid header: {}", e)))?;
+        if state.latest.is_none() {
+            self.trust_checkpoint(&mut state, client).await?;
+        }
+        if header.height().value() <= state.latest_height() {
+            return self.verify_backward(state, client, header).await;
+        }
+
+        let latest = state.latest_height();
+        let result = self.verify_forward(&mut state, client, header).await;
+        // Headers verified on the way count even if this one didn't pass
+        match &state.latest {
+            Some(newest) if newest.height().value() > latest => {
+                let newest = Checkpoint { height: newest.height().value(), hash: newest.hash() };
+                if let Err(e) = self.storage.put_trusted_header(&self.chain_id, &newest) {
+                    warn!(height = newest.height, "Failed to store the newest verified header: {}", e);
+                }
+            }
+            _ => {}
+        }
+        result
+    }
+
+    async fn trust_checkpoint(&self, state: &mut State, client: &dyn CelestiaNode) -> Result<(), NodeError> {
+        let checkpoint = match self.storage.get_trusted_header(&self.chain_id) {
+            Ok(Some(stored)) if stored.height > self.checkpoint.height => stored,
+            Ok(_) => self.checkpoint.clone(),
+            Err(e) => {
+                warn!("Failed to load the newest verified header, starting from the checkpoint: {}", e);
+                self.checkpoint.clone()
+            }
+        };
+        let header = client.header(checkpoint.height)
+            .await
+            .map_err(|e| e.context("Failed to get checkpoint header"))?;
+        if header.hash() != checkpoint.hash {
+            return Err(NodeError::Failed(format!(
+                "Checkpoint header at {} has hash {}, expected {}",
+                checkpoint.height, header.hash(), checkpoint.hash,
+            )));
+        }
+        header.validate().map_err(|e| NodeError::Failed(format!("Invalid checkpoint header: {}", e)))?;
+        info!(height = checkpoint.height, "Trusting checkpoint header");
+        state.trust(header);
+        Ok(())
+    }
+
+    async fn verify_forward(&self, state: &mut State, client: &dyn CelestiaNode, header: &ExtendedHeader) -> Result<(), NodeError> {
+        // Headers still to verify, the one we were asked about at the bottom
+        let mut pending = vec![header.clone()];
+        while let Some(untrusted) = pending.last() {
+            let trusted = state.latest.as_ref().expect("Checkpoint is trusted first");
+            let age = unix_now().saturating_sub(trusted.header.time.unix_timestamp().max(0) as u64);
+            if age > self.trusting_period.as_secs() {
+                return Err(NodeError::Failed(format!(
+                    "Newest verified header (at {}) is older than the trusting period, a newer checkpoint is needed",
+                    trusted.height().value(),
+                )));
+            }
+
+            let trusted_height = trusted.height().value();
+            let untrusted_height = untrusted.height().value();
+            match trusted.verify(untrusted) {
+                Ok(()) => {
+                    let verified = pending.pop().expect("Checked above");
+                    state.trust(verified);
+                }
+                // Adjacent headers only need the validator set hash to match, there's nothing in between to try
+                Err(e) if untrusted_height == trusted_height + 1 => {
+                    return Err(NodeError::Failed(format!("Header at {} failed verification: {}", untrusted_height, e)));
+                }
+                // Too few of the trusted validators signed it, verify a header halfway there first
+                Err(HeaderError::Verification(VerificationError::NotEnoughVotingPower(..))) => {
+                    let middle = client.header(trusted_height + (untrusted_height - trusted_height) / 2)
+                        .await
+                        .map_err(|e| e.context("Failed to get header"))?;
+                    middle.validate().map_err(|e| NodeError::Failed(format!("Invalid header: {}", e)))?;
+                    pending.push(middle);
+                }
+                Err(e) => {
+                    return Err(NodeError::Failed(format!("Header at {} failed verification: {}", untrusted_height, e)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Takes the lock rather than borrowing it so it can be let go between batches, a long walk
+    // back would otherwise hold up every other verification and keeping up with the head.
+    async fn verify_backward(&self, mut state: MutexGuard<'_, State>, client: &dyn CelestiaNode, header: &ExtendedHeader) -> Result<(), NodeError> {
+        let height = header.height().value();
+        loop {
+            if let Some(hash) = state.hashes.get(&height) {
+                return if *hash == header.hash() {
+                    Ok(())
+                } else {
+                    Err(NodeError::Failed(format!("Header at {} has hash {}, expected {}", height, header.hash(), hash)))
+                };
+            }
+            // The closest verified header after this one, there's always the newest one. Looked up
+            // again every batch since other verifications may have come closer in the meantime.
+            let (upper_height, upper_hash) = state.hashes.range(height + 1..)
+                .next()
+                .map(|(height, hash)| (*height, *hash))
+                .expect("Newest verified header is after the header");
+
+            let lower_height = upper_height.saturating_sub(BACKWARD_BATCH).max(height);
+            let lower = if lower_height == height {
+                header.clone()
+            } else {
+                client.header(lower_height)
+                    .await
+                    .map_err(|e| e.context("Failed to get header"))?
+            };
+            // Everything after `lower` up to and including the verified header
+            let rest = client.headers_between(&lower, upper_height + 1)
+                .await
+                .map_err(|e| e.context("Failed to get headers"))?;
+            let mut headers = vec![lower];
+            headers.extend(rest);
+
+            let top = headers.last().expect("Has at least `lower`");
+            if top.height().value() != upper_height || top.hash() != upper_hash {
+                return Err(NodeError::Failed(format!("Header at {} doesn't match the verified one", upper_height)));
+            }
+            for pair in headers.windows(2).rev() {
+                let (below, above) = (&pair[0], &pair[1]);
+                let link = above.header.last_block_id.map(|id| id.hash);
+                if below.height().value() + 1 != above.height().value() || link != Some(below.hash()) {
+                    return Err(NodeError::Failed(format!("Header at {} isn't linked to the one after it", below.height().value())));
+                }
+            }
+
+            state.hashes.insert(lower_height, headers[0].hash());
+            if lower_height == height {
+                return Ok(());
+            }
+            drop(state);
+            state = self.state.lock().await;
+        }
+    }
+}
+
+impl State {
+    fn latest_height(&self) -> u64 {
+        self.latest.as_ref().map_or(0, |latest| latest.height().value())
+    }
+
+    fn trust(&mut self, header: ExtendedHeader) {
+        let height = header.height().value();
+        self.hashes.insert(height, header.hash());
+        if self.latest.as_ref().is_none_or(|latest| height > latest.height().value()) {
+            self.latest = Some(header);
+        }
+    }
+}
+
+// Parses a --trusted-checkpoint value, CHAIN_ID=HEIGHT:HASH with the hash in hex
+pub fn parse_checkpoint(value: &str) -> Result<(String, Checkpoint), String> {
+    let invalid = || "Expected CHAIN_ID=HEIGHT:HASH".to_string();
+    let (chain_id, checkpoint) = value.split_once('=').ok_or_else(invalid)?;
+    let (height, hash) = checkpoint.split_once(':').ok_or_else(invalid)?;
+    if chain_id.is_empty() {
+        return Err(invalid());
+    }
+    let height = height.parse().map_err(|_| format!("Invalid height {}", height))?;
+    let hash = hash.trim_start_matches("0x").to_uppercase().parse()
+        .map_err(|_| format!("Invalid header hash {}", hash))?;
+    Ok((chain_id.to_string(), Checkpoint { height, hash }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use celestia_types::blob::{Blob, Commitment};
+    use celestia_types::nmt::{Namespace, NamespaceProof};
+    use celestia_types::test_utils::{unverify, ExtendedHeaderGenerator};
+    use tendermint::block::CommitSig;
+    use tendermint::Time;
+    use tokio::sync::Semaphore;
+    use crate::storage::MemoryStore;
+
+    const TRUSTING_PERIOD: Duration = Duration::from_secs(14 * 24 * 3600);
+
+    // Serves a generated chain, starting at height 1, and keeps track of the heights asked for one by one
+    struct Chain {
+        headers: Vec<ExtendedHeader>,
+        requested: std::sync::Mutex<Vec<u64>>,
+        // Batches of headers wait for a permit, if set
+        gate: Option<Arc<Semaphore>>,
+    }
+
+    impl Chain {
+        fn new(headers: Vec<ExtendedHeader>) -> Self {
+            Chain { headers, requested: Default::default(), gate: None }
+        }
+
+        fn at(&self, height: u64) -> &ExtendedHeader {
+            &self.headers[height as usize - 1]
+        }
+
+        fn checkpoint(&self, height: u64) -> Checkpoint {
+            Checkpoint { height, hash: self.at(height).hash() }
+        }
+
+        fn requested(&self) -> Vec<u64> {
+            std::mem::take(&mut self.requested.lock().unwrap())
+        }
+    }
+
+    #[tonic::async_trait]
+    impl CelestiaNode for Chain {
+        async fn local_head(&self) -> Result<ExtendedHeader, NodeError> {
+            Ok(self.headers.last().unwrap().clone())
+        }
+
+        async fn network_head(&self) -> Result<ExtendedHeader, NodeError> {
+            Ok(self.headers.last().unwrap().clone())
+        }
+
+        async fn header(&self, height: u64) -> Result<ExtendedHeader, NodeError> {
+            self.requested.lock().unwrap().push(height);
+            Ok(self.at(height).clone())
+        }
+
+        async fn headers_between(&self, from: &ExtendedHeader, to: u64) -> Result<Vec<ExtendedHeader>, NodeError> {
+            if let Some(gate) = &self.gate {
+                gate.acquire().await.unwrap().forget();
+            }
+            Ok((from.height().value() + 1..to).map(|height| self.at(height).clone()).collect())
+        }
+
+        async fn blob(&self, _height: u64, _namespace: Namespace, _commitment: Commitment) -> Result<Blob, NodeError> {
+            Err(NodeError::Failed("No blobs".to_string()))
+        }
+
+        async fn blob_proof(&self, _height: u64, _namespace: Namespace, _commitment: Commitment) -> Result<Vec<NamespaceProof>, NodeError> {
+            Err(NodeError::Failed("No blobs".to_string()))
+        }
+    }
+
+    // Signed by validators the trusted ones know nothing about
+    fn sign_by_strangers(header: &mut ExtendedHeader) {
+        unverify(header);
+        if let CommitSig::BlockIdFlagCommit { validator_address, .. } = &mut header.commit.signatures[0] {
+            *validator_address = header.header.proposer_address;
+        }
+    }
+
+    fn light_client(checkpoint: Checkpoint, storage: Arc<dyn JobStore>) -> LightClient {
+        LightClient::new("private".to_string(), checkpoint, TRUSTING_PERIOD, storage)
+    }
+
+    fn stored_height(storage: &dyn JobStore) -> Option<u64> {
+        storage.get_trusted_header("private").unwrap().map(|header| header.height)
+    }
+
+    #[tokio::test]
+    async fn skips_ahead_with_the_same_validators() {
+        let chain = Chain::new(ExtendedHeaderGenerator::new().next_many(20));
+        let storage = Arc::new(MemoryStore::new());
+        let client = light_client(chain.checkpoint(5), storage.clone());
+
+        client.verify(&chain, chain.at(20)).await.unwrap();
+        // Only the checkpoint had to be fetched
+        assert_eq!(chain.requested(), [5]);
+        assert_eq!(stored_height(storage.as_ref()), Some(20));
+
+        // Already verified
+        client.verify(&chain, chain.at(20)).await.unwrap();
+        assert!(chain.requested().is_empty());
+    }
+
+    #[tokio::test]
+    async fn bisects_when_it_cant_skip_ahead() {
+        let mut headers = ExtendedHeaderGenerator::new().next_many(20);
+        let mut forged = headers[19].clone();
+        sign_by_strangers(&mut forged);
+        headers[19] = forged.clone();
+        let chain = Chain::new(headers);
+        let storage = Arc::new(MemoryStore::new());
+        let client = light_client(chain.checkpoint(5), storage.clone());
+
+        let error = client.verify(&chain, &forged).await.unwrap_err();
+        assert!(matches!(&error, NodeError::Failed(e) if e.starts_with("Header at 20 failed verification")), "{}", error);
+        // Halfway there each time, until there's nothing in between
+        assert_eq!(chain.requested(), [5, 12, 16, 18, 19]);
+        // What was verified on the way is kept
+        assert_eq!(stored_height(storage.as_ref()), Some(19));
+    }
+
+    #[tokio::test]
+    async fn fails_without_bisecting_on_other_errors() {
+        let mut headers = ExtendedHeaderGenerator::new().next_many(20);
+        // A trusted validator's signature that doesn't check out
+        unverify(&mut headers[19]);
+        let chain = Chain::new(headers);
+        let client = light_client(chain.checkpoint(5), Arc::new(MemoryStore::new()));
+
+        let error = client.verify(&chain, chain.at(20)).await.unwrap_err();
+        assert!(error.to_string().contains("signature verification failed"), "{}", error);
+        // Nothing in between would help
+        assert_eq!(chain.requested(), [5]);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_skip_ahead_past_the_trusting_period() {
+        let mut generator = ExtendedHeaderGenerator::new();
+        let month_ago = (Time::now() - Duration::from_secs(30 * 24 * 3600)).unwrap();
+        generator.set_time(month_ago, Duration::from_secs(1));
+        let chain = Chain::new(generator.next_many(10));
+        let client = light_client(chain.checkpoint(2), Arc::new(MemoryStore::new()));
+
+        let error = client.verify(&chain, chain.at(10)).await.unwrap_err();
+        assert!(error.to_string().contains("older than the trusting period"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn verifies_older_headers_along_hash_links() {
+        let mut generator = ExtendedHeaderGenerator::new();
+        // More than one batch back from the checkpoint
+        let chain = Chain::new(generator.next_many(300));
+        let client = light_client(chain.checkpoint(300), Arc::new(MemoryStore::new()));
+
+        client.verify(&chain, chain.at(10)).await.unwrap();
+        assert_eq!(chain.requested(), [300, 300 - BACKWARD_BATCH]);
+        client.verify(&chain, chain.at(10)).await.unwrap();
+        assert!(chain.requested().is_empty());
+
+        // Same height, but not the header the chain links to
+        let forged = generator.another_of(chain.at(150));
+        let error = client.verify(&chain, &forged).await.unwrap_err();
+        assert_eq!(error.to_string(), "Header at 150 isn't linked to the one after it");
+        let error = client.verify(&chain, &generator.another_of(chain.at(10))).await.unwrap_err();
+        assert!(error.to_string().starts_with("Header at 10 has hash"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn lets_others_verify_between_batches_back() {
+        let gate = Arc::new(Semaphore::new(0));
+        let mut chain = Chain::new(ExtendedHeaderGenerator::new().next_many(610));
+        chain.gate = Some(gate.clone());
+        let chain = Arc::new(chain);
+        let client = Arc::new(light_client(chain.checkpoint(600), Arc::new(MemoryStore::new())));
+
+        // Three batches back, held up fetching the first
+        let back = tokio::spawn({
+            let (client, chain) = (client.clone(), chain.clone());
+            async move { client.verify(chain.as_ref(), chain.at(10)).await }
+        });
+        while chain.requested.lock().unwrap().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        let forward = tokio::spawn({
+            let (client, chain) = (client.clone(), chain.clone());
+            async move { client.verify(chain.as_ref(), chain.at(610)).await }
+        });
+        tokio::task::yield_now().await;
+
+        // Gets its turn once the first batch is done
+        gate.add_permits(1);
+        tokio::time::timeout(Duration::from_secs(5), forward).await
+            .expect("Waited for the whole walk back")
+            .unwrap()
+            .unwrap();
+        assert!(!back.is_finished());
+
+        gate.add_permits(2);
+        back.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_checkpoint_with_the_wrong_hash() {
+        let chain = Chain::new(ExtendedHeaderGenerator::new().next_many(10));
+        let checkpoint = Checkpoint { height: 5, hash: chain.at(6).hash() };
+        let client = light_client(checkpoint, Arc::new(MemoryStore::new()));
+
+        let error = client.verify(&chain, chain.at(10)).await.unwrap_err();
+        assert!(error.to_string().starts_with("Checkpoint header at 5 has hash"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn resumes_from_the_newest_stored_header() {
+        let chain = Chain::new(ExtendedHeaderGenerator::new().next_many(20));
+        let storage = Arc::new(MemoryStore::new());
+        storage.put_trusted_header("private", &chain.checkpoint(15)).unwrap();
+        light_client(chain.checkpoint(5), storage.clone())
+            .verify(&chain, chain.at(20)).await.unwrap();
+        assert_eq!(chain.requested(), [15]);
+
+        // A newer checkpoint wins over what's stored
+        storage.put_trusted_header("private", &chain.checkpoint(3)).unwrap();
+        light_client(chain.checkpoint(5), storage.clone())
+            .verify(&chain, chain.at(20)).await.unwrap();
+        assert_eq!(chain.requested(), [5]);
+    }
+
+    #[test]
+    fn parses_checkpoints() {
+        let hash = "0x".to_string() + &"ab".repeat(32);
+        let (chain_id, checkpoint) = parse_checkpoint(&format!("mocha-4={}:{}", 100, hash)).unwrap();
+        assert_eq!(chain_id, "mocha-4");
+        assert_eq!(checkpoint.height, 100);
+        assert_eq!(checkpoint.hash, Hash::Sha256([0xab; 32]));
+        for invalid in ["mocha-4", "=100:ab", "mocha-4=100", "mocha-4=x:ab", "mocha-4=100:zz"] {
+            assert!(parse_checkpoint(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
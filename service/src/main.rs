@@ -1,168 +1,836 @@
+// tonic::Status is what every RPC returns, boxing it everywhere in between gains nothing
+#![allow(clippy::result_large_err)]
+
 use std::sync::Arc;
-use tonic::{transport::Server, Request, Response, Status};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::net::SocketAddr;
+use std::ops::Bound;
+use std::path::PathBuf;
+use std::time::Duration;
+use tonic::{service::interceptor::InterceptedService, transport::{Certificate, Identity, Server, ServerTlsConfig}, Request, Response, Status};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info, warn, Instrument, Span};
 
 pub mod eqs {
     include!("generated/eqs.rs");
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("generated/eqs_descriptor.bin");
 }
 use eqs::inclusion_server::{Inclusion, InclusionServer};
-use eqs::{GetKeccakInclusionRequest, GetKeccakInclusionResponse, get_keccak_inclusion_response::{ResponseValue, Status as ResponseStatus}};
-
-use celestia_rpc::{BlobClient, Client, HeaderClient};
-use celestia_types::nmt::{Namespace, NamespacedHashExt};
-use celestia_types::blob::Commitment;
-use tendermint::{hash::Algorithm, Hash as TmHash};
-use tendermint_proto::{
-    v0_37::{types::BlockId as RawBlockId, version::Consensus as RawConsensusVersion},
-    Protobuf,
-};
-use std::cmp::max;
-use clap::{Parser};
-use nmt_rs::{
-    simple_merkle::{db::MemDb, proof::Proof, tree::{MerkleTree, MerkleHash}},
-    TmSha2Hasher,
-};
-use sp1_sdk::{ProverClient, SP1Proof, SP1ProofWithPublicValues, SP1Stdin, Prover, NetworkProver};
-
-use eq_common::{KeccakInclusionToDataRootProofInput, create_inclusion_proof_input};
-use serde::{Serialize, Deserialize};
+use eqs::admin_server::AdminServer;
+use eqs::{GetKeccakInclusionRequest, GetKeccakInclusionResponse, KeccakInclusionProof, Priority, ProofMode, WatchKeccakInclusionResponse, get_keccak_inclusion_response::{ResponseValue, Status as ResponseStatus}, watch_keccak_inclusion_response::ResponseValue as WatchResponseValue};
+
+mod job;
+use job::{Job, JobStatus, JobRecord, request_priority, resolve_job};
+mod storage;
+use storage::{JobStore, MemoryStore, SledStore};
+mod worker;
+use worker::Worker;
+mod admin;
+use admin::{AdminService, check_admin_token};
+mod gc;
+use gc::RetentionPolicy;
+mod bundle;
+mod queue;
+mod auth;
+use auth::{ApiClient, ApiKeys, check_api_key};
+mod gateway;
+use gateway::AdminRoutes;
+mod dashboard;
+use dashboard::Dashboard;
+mod health;
+use health::HealthChecks;
+mod metrics;
+use metrics::MetricsState;
+mod telemetry;
+use telemetry::LogFormat;
+mod node;
+use node::{Networks, NodeClient};
+mod light_client;
+use light_client::{Checkpoint, LightClient};
+mod prover;
+#[cfg(test)]
+mod test_utils;
+use prover::SuccinctNetwork;
+use queue::{JobQueue, QueueLimits, QueueSlot};
+
+use clap::{Parser, Subcommand};
+use sp1_sdk::{ProverClient, SP1Proof, SP1ProofWithPublicValues, Prover, HashableKey};
+
+use eq_common::decode_inclusion_proof_output;
 
 const KECCAK_INCLUSION_ELF: &[u8] = include_bytes!("../../target/elf-compilation/riscv32im-succinct-zkvm-elf/release/eq-program-keccak-inclusion");
 
-#[derive(Serialize, Deserialize)]
-pub struct Job {
-    pub height: u64,
-    pub namespace: Vec<u8>,
-    pub commitment: Vec<u8>,
+// Break the proof out into the pieces a non-Rust client (or an onchain verifier) needs
+fn proof_details(proof: &SP1ProofWithPublicValues, vkey_hash: &str) -> Result<KeccakInclusionProof, Status> {
+    let (proof_mode, proof_bytes) = match &proof.proof {
+        SP1Proof::Groth16(_) => (ProofMode::Groth16, proof.bytes()),
+        SP1Proof::Plonk(_) => (ProofMode::Plonk, proof.bytes()),
+        // Only Groth16 and Plonk proofs can be verified onchain
+        SP1Proof::Compressed(_) => (ProofMode::Compressed, Vec::new()),
+        SP1Proof::Core(_) => (ProofMode::Core, Vec::new()),
+    };
+
+    let public_values = proof.public_values.to_vec();
+    let output = decode_inclusion_proof_output(&public_values)
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(KeccakInclusionProof {
+        proof_bytes,
+        public_values,
+        keccak_hash: output.keccak_hash.to_vec(),
+        data_root: output.data_root.to_vec(),
+        vkey_hash: vkey_hash.to_string(),
+        proof_mode: proof_mode as i32,
+    })
 }
 
-#[derive(Serialize, Deserialize)]
-pub enum JobStatus {
-    // The Succinct Network job ID
-    Pending(String),
-    // For now we'll use the SP1ProofWithPublicValues as the proof
-    // Ideally we only want the public values + whatever is needed to verify the proof
-    // They don't seem to provide a type for that.
-    Completed(SP1ProofWithPublicValues), 
-    Failed(String),
+fn load_proof(storage: &dyn JobStore, key: &[u8]) -> Result<SP1ProofWithPublicValues, Status> {
+    storage.get_proof(key)?
+        .ok_or_else(|| Status::internal("Proof for completed job is missing"))
 }
+
+fn watch_response(storage: &dyn JobStore, key: &[u8], record: &JobRecord, vkey_hash: &str) -> Result<WatchKeccakInclusionResponse, Status> {
+    let (response_value, proof_details) = match &record.status {
+        JobStatus::Queued | JobStatus::Fetching | JobStatus::Pruned => (None, None),
+        JobStatus::Proving(proof_id) => (Some(WatchResponseValue::ProofId(proof_id.clone())), None),
+        JobStatus::Completed => {
+            let proof = load_proof(storage, key)?;
+            (
+                Some(WatchResponseValue::Proof(bincode::serialize(&proof).map_err(|e| Status::internal(e.to_string()))?)),
+                Some(proof_details(&proof, vkey_hash)?),
+            )
+        }
+        JobStatus::Failed(error) => (Some(WatchResponseValue::ErrorMessage(error.clone())), None),
+    };
+    Ok(WatchKeccakInclusionResponse {
+        stage: record.status.stage() as i32,
+        response_value,
+        proof_details,
+        job_id: record.job_id.clone(),
+    })
+}
+
 pub struct InclusionService {
-    client: Arc<Client>,
-    db: sled::Db,
+    storage: Arc<dyn JobStore>,
+    vkey_hash: String,
+    queue: Arc<JobQueue>,
+    networks: Arc<Networks>,
+    // Keys of jobs whose status was just updated by the worker
+    job_updates: broadcast::Sender<Vec<u8>>,
+}
+
+impl InclusionService {
+    // Look up the job, queueing it for the worker if we haven't seen it before
+    // or its proof has since been pruned
+    fn submit_job(&self, job: Job, key: &[u8], priority: Priority, client: Option<&ApiClient>) -> Result<JobRecord, Status> {
+        if let Some(client) = client {
+            client.check_priority(priority)?;
+        }
+        if let Some(existing) = self.storage.get_job(key)? {
+            if !matches!(existing.status, JobStatus::Pruned) {
+                return self.raise_priority(key, existing, priority);
+            }
+        }
+        // Only turn away jobs that would need a spot in the queue
+        let slot = self.queue.reserve().map_err(|_| Status::resource_exhausted(format!(
+            "Too many jobs are waiting ({}), try again later", self.queue.len()
+        )))?;
+        // Only jobs that need proving count against the client's quotas
+        let charge = client.map(|client| client.charge_job(key, self.storage.as_ref())).transpose()?;
+
+        let (record, queued) = self.queue_job(job, key, priority, slot)?;
+        // Refunded otherwise
+        if let Some(charge) = charge.filter(|_| queued) {
+            charge.keep();
+        }
+        Ok(record)
+    }
+
+    // Returns the job's record and whether we were the ones to queue it
+    fn queue_job(&self, job: Job, key: &[u8], priority: Priority, slot: QueueSlot<'_>) -> Result<(JobRecord, bool), Status> {
+        let queued = JobRecord::queued(key, priority);
+        let record = match self.storage.insert_job(key, &queued)? {
+            None => queued,
+            Some(existing) if matches!(existing.status, JobStatus::Pruned) => {
+                let mut requeued = existing.clone();
+                requeued.priority = priority;
+                requeued.update(JobStatus::Queued);
+                // Someone else requeued it first
+                if !self.storage.replace_job(key, &existing, &requeued)? {
+                    let record = self.storage.get_job(key)?
+                        .ok_or_else(|| Status::not_found("Job was removed"))?;
+                    return Ok((record, false));
+                }
+                let _ = self.job_updates.send(key.to_vec());
+                requeued
+            }
+            Some(existing) => return Ok((self.raise_priority(key, existing, priority)?, false)),
+        };
+        info!(height = job.height, proof_mode = ?job.proof_mode, priority = ?priority, "Queued job");
+        slot.push(job, priority);
+        Ok((record, true))
+    }
+
+    // Moves a job that's still waiting ahead if it's requested with a higher priority than before
+    fn raise_priority(&self, key: &[u8], existing: JobRecord, priority: Priority) -> Result<JobRecord, Status> {
+        if existing.status.is_terminal() || queue::lane(priority) >= queue::lane(existing.priority) {
+            return Ok(existing);
+        }
+        let mut raised = existing.clone();
+        raised.priority = priority;
+        // If the job changed in the meantime it has most likely been started, so there's nothing to raise
+        if !self.storage.replace_job(key, &existing, &raised)? {
+            return Ok(existing);
+        }
+        self.queue.promote(key, priority);
+        Ok(raised)
+    }
+
+    // The Inclusion RPCs, minus the metrics
+    fn keccak_inclusion(&self, job: Job, key: &[u8], request: &GetKeccakInclusionRequest, client: Option<&ApiClient>) -> Result<GetKeccakInclusionResponse, Status> {
+        let JobRecord { job_id, status, .. } = self.submit_job(job, key, request_priority(request)?, client)?;
+        Span::current().record("job_id", job_id.as_str());
+
+        match status {
+            JobStatus::Queued | JobStatus::Fetching | JobStatus::Pruned => {
+                Ok(GetKeccakInclusionResponse {
+                    status: ResponseStatus::Waiting as i32,
+                    response_value: None,
+                    proof_details: None,
+                    job_id,
+                    queue_position: self.queue.position(key).map(|position| position as u64),
+                })
+            }
+            JobStatus::Proving(proof_id) => {
+                Ok(GetKeccakInclusionResponse {
+                    status: ResponseStatus::Waiting as i32,
+                    response_value: Some(ResponseValue::ProofId(proof_id)),
+                    proof_details: None,
+                    job_id,
+                    queue_position: None,
+                })
+            }
+            JobStatus::Completed => {
+                let proof = load_proof(self.storage.as_ref(), key)?;
+                Ok(GetKeccakInclusionResponse {
+                    status: ResponseStatus::Complete as i32,
+                    response_value: Some(ResponseValue::Proof(bincode::serialize(&proof).map_err(|e| Status::internal(e.to_string()))?)),
+                    proof_details: Some(proof_details(&proof, &self.vkey_hash)?),
+                    job_id,
+                    queue_position: None,
+                })
+            }
+            JobStatus::Failed(error) => {
+                Ok(GetKeccakInclusionResponse {
+                    status: ResponseStatus::Failed as i32,
+                    response_value: Some(ResponseValue::ErrorMessage(error)),
+                    proof_details: None,
+                    job_id,
+                    queue_position: None,
+                })
+            }
+        }
+    }
+
+    fn watch_job(&self, job: Job, key: Vec<u8>, request: &GetKeccakInclusionRequest, client: Option<&ApiClient>) -> Result<ReceiverStream<Result<WatchKeccakInclusionResponse, Status>>, Status> {
+        // Subscribe before submitting so we can't miss a transition in between
+        let mut job_updates = self.job_updates.subscribe();
+        let mut record = self.submit_job(job, &key, request_priority(request)?, client)?;
+        Span::current().record("job_id", record.job_id.as_str());
+
+        let storage = self.storage.clone();
+        let vkey_hash = self.vkey_hash.clone();
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut last_stage = None;
+            loop {
+                let stage = record.status.stage();
+                if last_stage != Some(stage) {
+                    // Stop if the client hung up
+                    if tx.send(watch_response(storage.as_ref(), &key, &record, &vkey_hash)).await.is_err() {
+                        return;
+                    }
+                    last_stage = Some(stage);
+                }
+                if record.status.is_terminal() {
+                    return;
+                }
+
+                match job_updates.recv().await {
+                    Ok(updated_key) if updated_key != key => continue,
+                    // Either our job was updated or we missed some updates, re-read it either way
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+                record = match storage.get_job(&key) {
+                    Ok(Some(record)) => record,
+                    Ok(None) => {
+                        let _ = tx.send(Err(Status::not_found("Job was removed"))).await;
+                        return;
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Err(e.into())).await;
+                        return;
+                    }
+                };
+            }
+        }.in_current_span());
+
+        Ok(ReceiverStream::new(rx))
+    }
 }
 
 #[tonic::async_trait]
 impl Inclusion for InclusionService {
+    #[tracing::instrument(skip_all, fields(job_id))]
     async fn get_keccak_inclusion(
         &self,
         request: Request<GetKeccakInclusionRequest>,
     ) -> Result<Response<GetKeccakInclusionResponse>, Status> {
+        let client = request.extensions().get::<Arc<ApiClient>>().cloned();
+        let request = request.into_inner();
+        let resolved = resolve_job(self.storage.as_ref(), &request, &self.networks);
+        let proof_mode = resolved.as_ref().ok().map(|(job, _)| job.proof_mode);
+        let response = resolved.and_then(|(job, key)| self.keccak_inclusion(job, &key, &request, client.as_deref()));
 
+        let status = match &response {
+            Ok(response) => format!("{:?}", response.status()),
+            Err(e) => format!("{:?}", e.code()),
+        };
+        metrics::record_request("GetKeccakInclusion", &status, proof_mode);
+        response.map(Response::new)
+    }
 
-        let request = request.into_inner();
+    type WatchKeccakInclusionStream = ReceiverStream<Result<WatchKeccakInclusionResponse, Status>>;
 
-        let job_from_db = self.db.get(&bincode::serialize(&Job {
-            height: request.height,
-            namespace: request.namespace.clone(),
-            commitment: request.commitment.clone(),
-        }).map_err(|e| Status::internal(e.to_string()))?).map_err(|e| Status::internal(e.to_string()))?;
-
-        if let Some(job) = job_from_db {
-            let job: JobStatus = bincode::deserialize(&job)
-                .map_err(|e| Status::internal(e.to_string()))?;
-            match job {
-                JobStatus::Pending(job_id) => {
-                    return Ok(Response::new(GetKeccakInclusionResponse { 
-                        status: ResponseStatus::Waiting as i32, 
-                        response_value: Some(ResponseValue::ProofId(job_id))
-                    }));
-                }
-                JobStatus::Completed(proof) => {
-                    return Ok(Response::new(GetKeccakInclusionResponse { 
-                        status: ResponseStatus::Complete as i32, 
-                        response_value: Some(ResponseValue::Proof(bincode::serialize(&proof).map_err(|e| Status::internal(e.to_string()))?))
-                    }));
-                }
-                JobStatus::Failed(error) => {
-                    return Ok(Response::new(GetKeccakInclusionResponse { 
-                        status: ResponseStatus::Failed as i32, 
-                        response_value: Some(ResponseValue::ErrorMessage(error))
-                    }));
-                }
-            };
-        }
+    #[tracing::instrument(skip_all, fields(job_id))]
+    async fn watch_keccak_inclusion(
+        &self,
+        request: Request<GetKeccakInclusionRequest>,
+    ) -> Result<Response<Self::WatchKeccakInclusionStream>, Status> {
+        let client = request.extensions().get::<Arc<ApiClient>>().cloned();
+        let request = request.into_inner();
+        let resolved = resolve_job(self.storage.as_ref(), &request, &self.networks);
+        let proof_mode = resolved.as_ref().ok().map(|(job, _)| job.proof_mode);
+        let response = resolved.and_then(|(job, key)| self.watch_job(job, key, &request, client.as_deref()));
 
-        let height = request.height;
-        let commitment = Commitment::new(
-            request.commitment
-            .try_into()
-            .map_err(|_| Status::invalid_argument("Invalid commitment"))?
-        );
-        let namespace = Namespace::from_raw(&request.namespace)
-            .map_err(|e| Status::invalid_argument(e.to_string()))?;
-
-        let blob = self.client.blob_get(height, namespace, commitment).await
-            .map_err(|e| Status::internal(e.to_string()))?;
-
-        // Get the ExtendedHeader
-        let header = self.client.header_get_by_height(height)
-            .await
-            .map_err(|e| Status::internal(format!("Failed to get header: {}", e.to_string())))?;
-
-        let nmt_multiproofs = self.client
-            .blob_get_proof(height, namespace, commitment)
-            .await
-            .map_err(|e| Status::internal(format!("Failed to get blob proof: {}", e.to_string())))?;
-
-        let inclusion_proof_input = create_inclusion_proof_input(&blob, &header, nmt_multiproofs)
-            .map_err(|e| Status::internal(e.to_string()))?;
-
-        let network_prover = ProverClient::builder().network().build();
-        let (pk, vk) = network_prover.setup(KECCAK_INCLUSION_ELF);
-
-        let mut stdin = SP1Stdin::new();
-        stdin.write(&inclusion_proof_input);
-        let request_id = network_prover
-            .prove(&pk, &stdin)
-            .groth16()
-            .request_async()
-            .await
-            .unwrap(); // TODO: Handle this error
-        
-        // TODO: Write a pending job to the DB, and start a worker to wait for the proof and update DB when it's complete
-        // do so in a way so the service can recover from crashes, remember which jobs it's already started, and update DB when they're finished
-        
-        Ok(Response::new(GetKeccakInclusionResponse { status: 0, response_value: None }))
+        let status = match &response {
+            Ok(_) => "Ok".to_string(),
+            Err(e) => format!("{:?}", e.code()),
+        };
+        metrics::record_request("WatchKeccakInclusion", &status, proof_mode);
+        response.map(Response::new)
     }
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(short, long)]
-    db_path: String,
+    /// Directory of the sled DB that jobs and proofs are kept in
+    #[arg(short, long, required_unless_present = "in_memory")]
+    db_path: Option<String>,
+    /// A Celestia network to serve, as CHAIN_ID=URL[,URL...], repeat for more networks. For each
+    /// the first node that's up is used and the rest are fallbacks, moving back to an earlier one
+    /// once it's up again. Requests without a chain ID go to the first network. The node auth
+    /// token is read from CELESTIA_NODE_AUTH_TOKEN_<CHAIN_ID> (upper case, dashes as
+    /// underscores), falling back to CELESTIA_NODE_AUTH_TOKEN.
+    #[arg(long = "network", value_parser = node::parse_network, default_value = "celestia=ws://localhost:26658")]
+    networks: Vec<(String, Vec<String>)>,
+    /// The chain ID of the network that jobs stored before there could be more than one were for.
+    /// Needed to migrate a DB that has such jobs and to import version 1 bundles, nothing else
+    /// says which network they're on.
+    #[arg(long)]
+    legacy_chain_id: Option<String>,
+    /// Verify headers against this trusted header before proving anything under their data root, as
+    /// CHAIN_ID=HEIGHT:HASH. Repeat for more networks, headers on networks without one aren't verified.
+    /// The newest header verified since is kept in the DB and used instead once it's past the checkpoint.
+    #[arg(long = "trusted-checkpoint", value_parser = light_client::parse_checkpoint)]
+    trusted_checkpoints: Vec<(String, Checkpoint)>,
+    /// How long a verified header can be trusted to verify later ones. Keep it below the network's
+    /// unbonding period, which is 21 days on Celestia.
+    #[arg(long, default_value_t = 14 * 24 * 3600)]
+    trusting_period_secs: u64,
+    /// How often to check that the nodes are still there, they're reconnected to if not
+    #[arg(long, default_value_t = 10)]
+    node_probe_interval_secs: u64,
+    /// Keep jobs in memory only, they're lost on restart
+    #[arg(long, conflicts_with = "db_path")]
+    in_memory: bool,
+    /// Prune proofs completed more than this many seconds ago
+    #[arg(long)]
+    proof_max_age_secs: Option<u64>,
+    /// Prune the oldest proofs once all proofs together take up more than this many bytes
+    #[arg(long)]
+    proof_max_total_bytes: Option<u64>,
+    /// Only keep this many of the latest proofs per namespace
+    #[arg(long)]
+    proofs_per_namespace: Option<usize>,
+    /// How often to apply the proof retention policy
+    #[arg(long, default_value_t = 3600)]
+    gc_interval_secs: u64,
+    /// Jobs waiting to be started beyond this are turned away with RESOURCE_EXHAUSTED
+    #[arg(long, default_value_t = 1000)]
+    max_queued_jobs: usize,
+    /// How many jobs are fetched and proved at once
+    #[arg(long, default_value_t = 16)]
+    max_concurrent_jobs: usize,
+    /// Cap on how many running jobs can be high priority, e.g. to keep room for urgent jobs during a backfill
+    #[arg(long)]
+    max_concurrent_high_jobs: Option<usize>,
+    /// Cap on how many running jobs can be normal priority
+    #[arg(long)]
+    max_concurrent_normal_jobs: Option<usize>,
+    /// Cap on how many running jobs can be low priority
+    #[arg(long)]
+    max_concurrent_low_jobs: Option<usize>,
+    /// How many running jobs can be fetching from the Celestia node at once
+    #[arg(long, default_value_t = 4)]
+    max_concurrent_fetches: usize,
+    /// JSON file with the API keys clients need to send in the x-api-key metadata, along with
+    /// their quotas and rate limits. Anyone can use the service if this isn't set.
+    #[arg(long)]
+    api_keys: Option<PathBuf>,
+    /// PEM certificate and private key to serve over TLS, plaintext otherwise
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// PEM CA certificate(s) that client certificates must be signed by, for mutual TLS
+    #[arg(long, requires = "tls_cert")]
+    tls_client_ca: Option<PathBuf>,
+    /// Address to serve gRPC on. Off loopback it needs TLS if API keys or an admin token are set.
+    #[arg(long, default_value = "[::1]:50051")]
+    grpc_addr: SocketAddr,
+    /// How often to check the node, DB and prover network for grpc.health.v1.Health
+    #[arg(long, default_value_t = 10)]
+    health_check_interval_secs: u64,
+    /// Also serve the HTTP/JSON gateway and the dashboard on this address, over TLS with the
+    /// same certificate as the gRPC server if one is set
+    #[arg(long)]
+    http_addr: Option<SocketAddr>,
+    /// Serve Prometheus metrics at /metrics on this address
+    #[arg(long)]
+    metrics_addr: Option<SocketAddr>,
+    /// Write logs as plain text or as JSON lines
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Also export trace spans to this OTLP/gRPC collector, e.g. http://localhost:4317
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+    /// On SIGTERM or Ctrl-C, how long to wait for open requests and running jobs to finish
+    /// before stopping them. Stopped jobs resume from where they were on the next start.
+    #[arg(long, default_value_t = 60)]
+    drain_timeout_secs: u64,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+// Run instead of the service, against the same DB. Sled only allows one process
+// to open a DB at a time, so the service has to be stopped first.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write all completed jobs to a bundle file
+    Export {
+        /// Bundle file to write
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Verify and load the jobs in a bundle file
+    Import {
+        /// Bundle file to read
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+}
+
+async fn shutdown_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = sigterm.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+async fn stopped(mut shutdown: watch::Receiver<bool>) {
+    // An error means the sender is gone, which only happens once we're exiting anyway
+    let _ = shutdown.wait_for(|&stop| stop).await;
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let args = Args::parse();
-    let db = sled::open(args.db_path)?;
+    let tracer_provider = telemetry::init(args.log_format, args.otlp_endpoint.as_deref())?;
+
+    let legacy_chain_id = args.legacy_chain_id.as_deref();
+    let storage: Arc<dyn JobStore> = if args.in_memory {
+        Arc::new(MemoryStore::new())
+    } else {
+        // clap makes sure we have one or the other
+        Arc::new(SledStore::open(args.db_path.expect("No DB path provided"), legacy_chain_id)?)
+    };
 
-    let node_token = std::env::var("CELESTIA_NODE_AUTH_TOKEN").expect("Token not provided");
-    let client = Client::new("ws://localhost:26658", Some(&node_token))
-        .await
-        .expect("Failed creating celestia rpc client");
+    if let Some(command) = args.command {
+        // Bundles are checked against the local program, no need for the node or the prover network
+        let cpu_prover = ProverClient::builder().cpu().build();
+        let (_, vk) = cpu_prover.setup(KECCAK_INCLUSION_ELF);
+        match command {
+            Command::Export { output } => {
+                let writer = BufWriter::new(File::create(output)?);
+                let exported = bundle::export(storage.as_ref(), &vk.bytes32(), writer)?;
+                println!("Exported {} proofs", exported);
+            }
+            Command::Import { input } => {
+                let reader = BufReader::new(File::open(input)?);
+                let report = bundle::import(storage.as_ref(), &cpu_prover, &vk, &vk.bytes32(), legacy_chain_id, reader)?;
+                println!("Imported {} proofs, {} already present, {} failed", report.imported, report.skipped, report.failed);
+            }
+        }
+        return Ok(());
+    }
 
-    let addr = "[::1]:50051".parse()?;
-    let inclusion_service = InclusionService{
-        client: Arc::new(client),
-        db: db,
+    let api_keys = args.api_keys.map(ApiKeys::load).transpose()?.map(Arc::new);
+    // Clients would be sending their API keys or the admin token over the network in the clear
+    let sends_secrets = api_keys.is_some() || std::env::var_os("EQ_ADMIN_TOKEN").is_some();
+    if sends_secrets && args.tls_cert.is_none() && !args.grpc_addr.ip().is_loopback() {
+        return Err(format!(
+            "Refusing to serve gRPC on {} without TLS while API keys or an admin token are required, set --tls-cert and --tls-key or use a loopback address",
+            args.grpc_addr,
+        ).into());
+    }
+    if let Some(http_addr) = args.http_addr {
+        if sends_secrets && args.tls_cert.is_none() && !http_addr.ip().is_loopback() {
+            return Err(format!(
+                "Refusing to serve the HTTP gateway on {} without TLS while API keys or an admin token are required, set --tls-cert and --tls-key or use a loopback address",
+                http_addr,
+            ).into());
+        }
+    }
+
+    for (chain_id, _) in &args.trusted_checkpoints {
+        if !args.networks.iter().any(|(network, _)| network == chain_id) {
+            return Err(format!("Checkpoint given for {}, which isn't a configured network", chain_id).into());
+        }
+    }
+
+    let default_token = std::env::var("CELESTIA_NODE_AUTH_TOKEN").ok();
+    let nodes = args.networks.into_iter().map(|(chain_id, urls)| {
+        let token_var = format!("CELESTIA_NODE_AUTH_TOKEN_{}", chain_id.to_uppercase().replace('-', "_"));
+        let token = std::env::var(&token_var).ok().or_else(|| default_token.clone())
+            .unwrap_or_else(|| panic!("Token not provided, set {} or CELESTIA_NODE_AUTH_TOKEN", token_var));
+        let light_client = args.trusted_checkpoints.iter()
+            .find(|(network, _)| *network == chain_id)
+            .map(|(_, checkpoint)| LightClient::new(chain_id.clone(), checkpoint.clone(), Duration::from_secs(args.trusting_period_secs), storage.clone()));
+        Arc::new(NodeClient::new(chain_id, urls, Some(token), light_client))
+    }).collect();
+    let networks = Arc::new(Networks::new(nodes));
+    for node in networks.nodes() {
+        tokio::spawn(node.clone().maintain(Duration::from_secs(args.node_probe_interval_secs)));
+    }
+
+    let network_prover = ProverClient::builder().network().build();
+    // Where NetworkProver sends its requests
+    let prover_rpc_url = std::env::var("NETWORK_RPC_URL")
+        .unwrap_or_else(|_| health::DEFAULT_PROVER_RPC_URL.to_string());
+    let (pk, vk) = network_prover.setup(KECCAK_INCLUSION_ELF);
+
+    let running_per_priority = [
+        (Priority::High, args.max_concurrent_high_jobs),
+        (Priority::Normal, args.max_concurrent_normal_jobs),
+        (Priority::Low, args.max_concurrent_low_jobs),
+    ];
+    let queue = Arc::new(JobQueue::new(QueueLimits {
+        capacity: args.max_queued_jobs,
+        running: args.max_concurrent_jobs,
+        running_per_priority: running_per_priority.into_iter()
+            .filter_map(|(priority, cap)| Some((priority, cap?)))
+            .collect(),
+    }));
+    let (job_updates, _) = broadcast::channel(256);
+
+    // Pick back up any jobs that were in flight when the service last stopped
+    for entry in storage.jobs_from(Bound::Unbounded) {
+        let (_, job, record) = entry?;
+        if !record.status.is_terminal() {
+            queue.push(job, record.priority);
+        }
+    }
+
+    let worker = Worker::new(
+        networks.clone(),
+        storage.clone(),
+        Arc::new(SuccinctNetwork::new(network_prover, pk, prover_rpc_url.clone())),
+        job_updates.clone(),
+        queue.clone(),
+        args.max_concurrent_fetches,
+    );
+    let (shutdown_tx, shutdown) = watch::channel(false);
+    tokio::spawn(worker.clone().run(shutdown.clone()));
+
+    let retention_policy = RetentionPolicy {
+        max_age: args.proof_max_age_secs.map(Duration::from_secs),
+        max_total_size: args.proof_max_total_bytes,
+        keep_per_namespace: args.proofs_per_namespace,
+    };
+    if !retention_policy.is_empty() {
+        tokio::spawn(gc::run_gc(
+            storage.clone(),
+            retention_policy.clone(),
+            Duration::from_secs(args.gc_interval_secs),
+            job_updates.clone(),
+        ));
+    }
+
+    // Admin RPCs are only served when a token is configured
+    let admin_routes = std::env::var("EQ_ADMIN_TOKEN").ok().map(|token| AdminRoutes {
+        service: Arc::new(AdminService::new(storage.clone(), worker.clone(), queue.clone(), networks.clone(), retention_policy, job_updates.clone())),
+        token,
+    });
+    let admin_service = admin_routes.as_ref().map(|admin| {
+        InterceptedService::new(AdminServer::from_arc(admin.service.clone()), check_admin_token(admin.token.clone()))
+    });
+
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    tokio::spawn(health::run_health_checks(
+        health_reporter,
+        HealthChecks {
+            storage: storage.clone(),
+            networks: networks.clone(),
+            prover_rpc_url,
+        },
+        Duration::from_secs(args.health_check_interval_secs),
+    ));
+
+    // Both reflection versions, since older grpcurl releases only speak v1alpha
+    let reflection = || tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(eqs::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET);
+    let reflection_v1 = reflection().build_v1()?;
+    let reflection_v1alpha = reflection().build_v1alpha()?;
+
+    let dashboard = Dashboard {
+        storage: storage.clone(),
+        networks: networks.clone(),
+        queue: queue.clone(),
+        api_keys: api_keys.clone(),
     };
+    if let Some(metrics_addr) = args.metrics_addr {
+        let metrics = metrics::serve(metrics_addr, MetricsState { storage: storage.clone(), queue: queue.clone() });
+        tokio::spawn(async move {
+            if let Err(e) = metrics.await {
+                error!("Metrics server stopped: {}", e);
+            }
+        });
+    }
+
+    let inclusion_service = Arc::new(InclusionService{
+        storage: storage.clone(),
+        vkey_hash: vk.bytes32(),
+        queue,
+        networks,
+        job_updates,
+    });
+
+    if let Some(http_addr) = args.http_addr {
+        let tls = match (&args.tls_cert, &args.tls_key) {
+            (Some(cert), Some(key)) => Some(gateway::tls_config(cert, key, args.tls_client_ca.as_deref())?),
+            _ => None,
+        };
+        let gateway = gateway::serve(
+            http_addr,
+            tls,
+            inclusion_service.clone(),
+            api_keys.clone(),
+            admin_routes,
+            dashboard::routes(dashboard),
+            stopped(shutdown.clone()),
+        );
+        tokio::spawn(async move {
+            if let Err(e) = gateway.await {
+                error!("HTTP gateway stopped: {}", e);
+            }
+        });
+    }
+
+    let mut server = Server::builder();
+    if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+        let mut tls_config = ServerTlsConfig::new()
+            .identity(Identity::from_pem(std::fs::read(cert)?, std::fs::read(key)?));
+        // Clients without a certificate signed by the CA are turned away during the handshake
+        if let Some(client_ca) = &args.tls_client_ca {
+            tls_config = tls_config.client_ca_root(Certificate::from_pem(std::fs::read(client_ca)?));
+        }
+        server = server.tls_config(tls_config)?;
+    }
 
-    Server::builder()
-        .add_service(InclusionServer::new(inclusion_service))
-        .serve(addr)
-        .await?;
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down, no longer accepting requests");
+        let _ = shutdown_tx.send(true);
+    });
+
+    let serve = server
+        .add_service(InterceptedService::new(InclusionServer::from_arc(inclusion_service), check_api_key(api_keys)))
+        .add_optional_service(admin_service)
+        .add_service(health_service)
+        .add_service(reflection_v1)
+        .add_service(reflection_v1alpha)
+        .serve_with_shutdown(args.grpc_addr, stopped(shutdown.clone()));
+    tokio::pin!(serve);
+    // The server only finishes by itself if it fails
+    let server_finished = tokio::select! {
+        result = &mut serve => {
+            result?;
+            true
+        }
+        _ = stopped(shutdown.clone()) => false,
+    };
 
+    // Open requests (watch streams included) and running jobs get the same time to finish
+    let drain_timeout = Duration::from_secs(args.drain_timeout_secs);
+    let drain_server = async {
+        if server_finished {
+            return Ok(());
+        }
+        match tokio::time::timeout(drain_timeout, serve).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("Closing requests that are still open");
+                Ok(())
+            }
+        }
+    };
+    let (served, ()) = tokio::join!(drain_server, worker.drain(drain_timeout));
+    served?;
+    storage.flush()?;
+    info!("Shut down cleanly");
+
+    if let Some(tracer_provider) = tracer_provider {
+        tracer_provider.shutdown()?;
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::MemoryStore;
+    use test_utils::{EmptyNode, CHAIN_ID};
+
+    fn service(capacity: usize) -> InclusionService {
+        let node = NodeClient::connected(CHAIN_ID, Arc::new(EmptyNode));
+        InclusionService {
+            storage: Arc::new(MemoryStore::new()),
+            vkey_hash: "0x00".to_string(),
+            queue: Arc::new(test_utils::queue(capacity)),
+            networks: Arc::new(Networks::new(vec![Arc::new(node)])),
+            job_updates: broadcast::channel(16).0,
+        }
+    }
+
+    fn submit(service: &InclusionService, height: u64, priority: Priority) -> Result<JobRecord, Status> {
+        let job = test_utils::job(height);
+        let key = storage::job_key(&job);
+        service.submit_job(job, &key, priority, None)
+    }
+
+    fn set_status(service: &InclusionService, key: &[u8], status: JobStatus) {
+        let mut record = service.storage.get_job(key).unwrap().unwrap();
+        record.update(status);
+        service.storage.put_job(key, &record).unwrap();
+    }
+
+    #[test]
+    fn submit_job_dedupes() {
+        let service = service(10);
+        let first = submit(&service, 10, Priority::Normal).unwrap();
+        let second = submit(&service, 10, Priority::Normal).unwrap();
+        assert_eq!(first.job_id, second.job_id);
+        assert_eq!(service.queue.len(), 1);
+
+        submit(&service, 11, Priority::Normal).unwrap();
+        assert_eq!(service.queue.len(), 2);
+    }
+
+    #[test]
+    fn submit_job_raises_priority() {
+        let service = service(10);
+        submit(&service, 10, Priority::Normal).unwrap();
+        submit(&service, 11, Priority::Normal).unwrap();
+        let key = storage::job_key(&test_utils::job(11));
+        assert_eq!(service.queue.position(&key), Some(1));
+
+        let raised = submit(&service, 11, Priority::High).unwrap();
+        assert_eq!(raised.priority, Priority::High);
+        assert_eq!(service.storage.get_job(&key).unwrap().unwrap().priority, Priority::High);
+        assert_eq!(service.queue.position(&key), Some(0));
+
+        // Never lowered
+        let again = submit(&service, 11, Priority::Low).unwrap();
+        assert_eq!(again.priority, Priority::High);
+    }
+
+    #[test]
+    fn raise_priority_leaves_changed_jobs_alone() {
+        let service = service(10);
+        let stale = submit(&service, 10, Priority::Normal).unwrap();
+        let key = storage::job_key(&test_utils::job(10));
+        // The worker picked it up after we read it
+        set_status(&service, &key, JobStatus::Fetching);
+
+        let record = service.raise_priority(&key, stale, Priority::High).unwrap();
+        assert_eq!(record.priority, Priority::Normal);
+        let stored = service.storage.get_job(&key).unwrap().unwrap();
+        assert_eq!(stored.priority, Priority::Normal);
+        assert!(matches!(stored.status, JobStatus::Fetching));
+    }
+
+    #[test]
+    fn submit_job_leaves_finished_jobs_alone() {
+        let service = service(10);
+        submit(&service, 10, Priority::Normal).unwrap();
+        let key = storage::job_key(&test_utils::job(10));
+        service.queue.remove(&key);
+        set_status(&service, &key, JobStatus::Failed("oops".to_string()));
+
+        let record = submit(&service, 10, Priority::High).unwrap();
+        assert!(matches!(record.status, JobStatus::Failed(_)));
+        assert_eq!(record.priority, Priority::Normal);
+        assert_eq!(service.queue.len(), 0);
+    }
+
+    #[test]
+    fn submit_job_requeues_pruned_jobs() {
+        let service = service(10);
+        let first = submit(&service, 10, Priority::Normal).unwrap();
+        let key = storage::job_key(&test_utils::job(10));
+        service.queue.remove(&key);
+        set_status(&service, &key, JobStatus::Proving("request-1".to_string()));
+        set_status(&service, &key, JobStatus::Pruned);
+        let mut updates = service.job_updates.subscribe();
+
+        let requeued = submit(&service, 10, Priority::High).unwrap();
+        assert!(matches!(requeued.status, JobStatus::Queued));
+        assert_eq!(requeued.job_id, first.job_id);
+        assert_eq!(requeued.priority, Priority::High);
+        assert_eq!(requeued.proof_id, None);
+        assert_eq!(service.queue.position(&key), Some(0));
+        assert_eq!(updates.try_recv().unwrap(), key);
+    }
+
+    #[test]
+    fn submit_job_turns_away_new_jobs_when_full() {
+        let service = service(1);
+        submit(&service, 10, Priority::Normal).unwrap();
+        let status = submit(&service, 11, Priority::Normal).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(service.storage.get_job(&storage::job_key(&test_utils::job(11))).unwrap().is_none());
+
+        // Jobs we already have don't need room
+        submit(&service, 10, Priority::Normal).unwrap();
+    }
+}
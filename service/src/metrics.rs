@@ -0,0 +1,116 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use tracing::warn;
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    HistogramVec, IntCounterVec, IntGauge, TextEncoder, TEXT_FORMAT,
+};
+
+use crate::eqs::ProofMode;
+use crate::queue::JobQueue;
+use crate::storage::JobStore;
+
+/*
+    Prometheus metrics, served at /metrics. Everything to do with a job is labelled with
+    its proof mode (the ProofMode enum name), since that's what mostly decides how long
+    and how much a job takes. Registered with the default registry on first use.
+*/
+
+// Finished Inclusion requests. `status` is the response status (Waiting, Complete, Failed) of
+// a successful GetKeccakInclusion, and the gRPC code (Ok, NotFound, ...) for everything else.
+pub static REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "eqs_requests_total",
+        "Inclusion requests by method, status and proof mode",
+        &["method", "status", "proof_mode"]
+    )
+    .unwrap()
+});
+
+// `stage` is one of node_fetch, input_build or proving. Proving is measured from when the
+// request was accepted by the prover network, so it includes the time spent in its queue.
+pub static STAGE_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "eqs_job_stage_duration_seconds",
+        "How long each stage of a job took",
+        &["stage", "proof_mode"],
+        // 100ms up to about an hour
+        exponential_buckets(0.1, 2.0, 16).unwrap()
+    )
+    .unwrap()
+});
+
+pub static PROVER_CYCLES: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "eqs_prover_cycles",
+        "RISC-V cycles executed by the inclusion program, per job, as reported by the prover network",
+        &["proof_mode"],
+        // 100k up to about 3 billion
+        exponential_buckets(100_000.0, 2.0, 16).unwrap()
+    )
+    .unwrap()
+});
+
+// `method` is the Celestia node RPC method that failed
+pub static NODE_RPC_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "eqs_node_rpc_errors_total",
+        "Failed Celestia node RPC calls made while fetching job inputs",
+        &["method", "proof_mode"]
+    )
+    .unwrap()
+});
+
+// These two are only updated when scraped
+pub static QUEUE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("eqs_queue_depth", "Jobs waiting for the worker to start them").unwrap()
+});
+
+pub static DB_SIZE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("eqs_db_size_bytes", "Size of the job database on disk").unwrap()
+});
+
+pub fn proof_mode_label(proof_mode: Option<ProofMode>) -> String {
+    proof_mode.map_or_else(|| "Unknown".to_string(), |proof_mode| format!("{:?}", proof_mode))
+}
+
+pub fn record_request(method: &str, status: &str, proof_mode: Option<ProofMode>) {
+    REQUESTS.with_label_values(&[method, status, &proof_mode_label(proof_mode)]).inc();
+}
+
+#[derive(Clone)]
+pub struct MetricsState {
+    pub storage: Arc<dyn JobStore>,
+    pub queue: Arc<JobQueue>,
+}
+
+pub fn routes(state: MetricsState) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(state)
+}
+
+pub async fn serve(addr: SocketAddr, state: MetricsState) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, routes(state)).await
+}
+
+async fn metrics(State(state): State<MetricsState>) -> impl IntoResponse {
+    QUEUE_DEPTH.set(state.queue.len() as i64);
+    match state.storage.size_on_disk() {
+        Ok(size) => DB_SIZE.set(size as i64),
+        Err(e) => warn!("Failed to get DB size for metrics: {}", e),
+    }
+
+    match TextEncoder::new().encode_to_string(&prometheus::gather()) {
+        Ok(body) => ([(CONTENT_TYPE, TEXT_FORMAT)], body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,341 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use jsonrpsee_core::ClientError;
+use thiserror::Error;
+use tokio::sync::{watch, Notify};
+use tokio::time::timeout;
+use tracing::{info, warn};
+
+use celestia_rpc::{BlobClient, Client, HeaderClient};
+use celestia_types::blob::{Blob, Commitment};
+use celestia_types::nmt::{Namespace, NamespaceProof};
+use celestia_types::ExtendedHeader;
+
+use crate::light_client::LightClient;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+// How long a request waits for the connection to come back before giving up
+const CONNECT_WAIT: Duration = Duration::from_secs(10);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// How often to try getting back to a more preferred URL while connected to a fallback
+const FAILBACK_INTERVAL: Duration = Duration::from_secs(300);
+
+#[derive(Error, Debug)]
+pub enum NodeError {
+    // The node couldn't be reached or didn't answer in time, the same request may work later
+    #[error("{0}")]
+    Unavailable(String),
+
+    // Anything else, e.g. the node doesn't have what we asked for or gave us something we can't use
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl NodeError {
+    // Says what we were doing when it went wrong, keeping the kind of error
+    pub fn context(self, what: &str) -> Self {
+        match self {
+            NodeError::Unavailable(e) => NodeError::Unavailable(format!("{}: {}", what, e)),
+            NodeError::Failed(e) => NodeError::Failed(format!("{}: {}", what, e)),
+        }
+    }
+}
+
+impl From<ClientError> for NodeError {
+    fn from(e: ClientError) -> Self {
+        match e {
+            ClientError::Transport(_) | ClientError::RestartNeeded(_) | ClientError::RequestTimeout => NodeError::Unavailable(e.to_string()),
+            // Including errors the node answered with
+            _ => NodeError::Failed(e.to_string()),
+        }
+    }
+}
+
+// The Celestia node RPCs we use, a celestia_rpc::Client outside of tests
+#[tonic::async_trait]
+pub trait CelestiaNode: Send + Sync {
+    async fn local_head(&self) -> Result<ExtendedHeader, NodeError>;
+
+    async fn network_head(&self) -> Result<ExtendedHeader, NodeError>;
+
+    async fn header(&self, height: u64) -> Result<ExtendedHeader, NodeError>;
+
+    // The headers after `from` and before `to`
+    async fn headers_between(&self, from: &ExtendedHeader, to: u64) -> Result<Vec<ExtendedHeader>, NodeError>;
+
+    async fn blob(&self, height: u64, namespace: Namespace, commitment: Commitment) -> Result<Blob, NodeError>;
+
+    async fn blob_proof(&self, height: u64, namespace: Namespace, commitment: Commitment) -> Result<Vec<NamespaceProof>, NodeError>;
+}
+
+#[tonic::async_trait]
+impl CelestiaNode for Client {
+    async fn local_head(&self) -> Result<ExtendedHeader, NodeError> {
+        Ok(self.header_local_head().await?)
+    }
+
+    async fn network_head(&self) -> Result<ExtendedHeader, NodeError> {
+        Ok(self.header_network_head().await?)
+    }
+
+    async fn header(&self, height: u64) -> Result<ExtendedHeader, NodeError> {
+        Ok(self.header_get_by_height(height).await?)
+    }
+
+    async fn headers_between(&self, from: &ExtendedHeader, to: u64) -> Result<Vec<ExtendedHeader>, NodeError> {
+        Ok(self.header_get_range_by_height(from, to).await?)
+    }
+
+    async fn blob(&self, height: u64, namespace: Namespace, commitment: Commitment) -> Result<Blob, NodeError> {
+        Ok(self.blob_get(height, namespace, commitment).await?)
+    }
+
+    async fn blob_proof(&self, height: u64, namespace: Namespace, commitment: Commitment) -> Result<Vec<NamespaceProof>, NodeError> {
+        Ok(self.blob_get_proof(height, namespace, commitment).await?)
+    }
+}
+
+#[derive(Clone)]
+struct Connection {
+    url: String,
+    client: Arc<dyn CelestiaNode>,
+}
+
+/*
+    A Celestia node connection that survives the node going away. `maintain` probes the
+    connection in the background and, once it's lost, reconnects with backoff, trying each
+    of the configured URLs in order. So the first URL is preferred and the rest are fallbacks.
+    While on a fallback, the URLs before it are tried again every FAILBACK_INTERVAL and we
+    move back as soon as one of them works.
+    Nodes that turn out to be on a different network than `chain_id` are skipped.
+    With a light client, headers are only used once they've been verified by it.
+*/
+pub struct NodeClient {
+    chain_id: String,
+    urls: Vec<String>,
+    auth_token: Option<String>,
+    light_client: Option<LightClient>,
+    connection: watch::Sender<Option<Connection>>,
+    // Asks `maintain` to probe right away rather than waiting for the next interval
+    probe_now: Notify,
+}
+
+impl NodeClient {
+    pub fn new(chain_id: String, urls: Vec<String>, auth_token: Option<String>, light_client: Option<LightClient>) -> Self {
+        NodeClient {
+            chain_id,
+            urls,
+            auth_token,
+            light_client,
+            connection: watch::Sender::new(None),
+            probe_now: Notify::new(),
+        }
+    }
+
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    // Already connected to `node`, which is never replaced since there are no URLs to reconnect to
+    #[cfg(test)]
+    pub fn connected(chain_id: &str, node: Arc<dyn CelestiaNode>) -> Self {
+        let client = NodeClient::new(chain_id.to_string(), Vec::new(), None, None);
+        client.connection.send_replace(Some(Connection { url: "test".to_string(), client: node }));
+        client
+    }
+
+    // The client to make requests with, waiting a little if we're reconnecting
+    pub async fn client(&self) -> Result<Arc<dyn CelestiaNode>, NodeError> {
+        let mut connection = self.connection.subscribe();
+        let client = match timeout(CONNECT_WAIT, connection.wait_for(Option::is_some)).await {
+            Ok(Ok(connection)) => connection.as_ref().map(|connection| connection.client.clone()),
+            _ => None,
+        };
+        client.ok_or_else(|| NodeError::Unavailable("Not connected to a Celestia node".to_string()))
+    }
+
+    // Without a light client, headers are taken as the node gives them
+    pub async fn verify_header(&self, client: &dyn CelestiaNode, header: &ExtendedHeader) -> Result<(), NodeError> {
+        match &self.light_client {
+            Some(light_client) => light_client.verify(client, header).await,
+            None => Ok(()),
+        }
+    }
+
+    // Call when a request to the node fails, so a lost connection is noticed right away
+    pub fn report_error(&self) {
+        self.probe_now.notify_one();
+    }
+
+    // Whether the node we're connected to is responding
+    pub async fn check(&self) -> Result<(), String> {
+        let connection = self.connection.borrow().clone();
+        match connection {
+            Some(connection) => probe(connection.client.as_ref()).await.map(|_| ()).map_err(|e| e.to_string()),
+            None => Err("Not connected to a Celestia node".to_string()),
+        }
+    }
+
+    pub async fn maintain(self: Arc<Self>, probe_interval: Duration) {
+        let mut backoff = MIN_BACKOFF;
+        let mut last_failback = Instant::now();
+        loop {
+            let connection = self.connection.borrow().clone();
+            match connection {
+                Some(connection) => {
+                    let _ = timeout(probe_interval, self.probe_now.notified()).await;
+                    if last_failback.elapsed() >= FAILBACK_INTERVAL {
+                        last_failback = Instant::now();
+                        if let Some(preferred) = self.failback(&connection.url).await {
+                            info!(chain_id = self.chain_id.as_str(), url = preferred.url.as_str(), "Moved back to preferred Celestia node");
+                            self.connection.send_replace(Some(preferred));
+                            continue;
+                        }
+                    }
+                    match probe(connection.client.as_ref()).await {
+                        // Keeps the light client's trusted header from falling out of the trusting period
+                        Ok(head) => if let Some(light_client) = &self.light_client {
+                            if let Err(e) = light_client.sync_to(connection.client.as_ref(), &head).await {
+                                warn!(chain_id = self.chain_id.as_str(), url = connection.url.as_str(), "Failed to verify the node's head: {}", e);
+                            }
+                        },
+                        Err(e) => {
+                            warn!(chain_id = self.chain_id.as_str(), url = connection.url.as_str(), "Lost connection to Celestia node: {}", e);
+                            self.connection.send_replace(None);
+                        }
+                    }
+                }
+                None => match self.connect().await {
+                    Some(connection) => {
+                        info!(chain_id = self.chain_id.as_str(), url = connection.url.as_str(), "Connected to Celestia node");
+                        self.connection.send_replace(Some(connection));
+                        backoff = MIN_BACKOFF;
+                        last_failback = Instant::now();
+                    }
+                    None => {
+                        warn!(chain_id = self.chain_id.as_str(), "Couldn't connect to any Celestia node, retrying in {:?}", backoff);
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                },
+            }
+        }
+    }
+
+    async fn connect(&self) -> Option<Connection> {
+        for url in &self.urls {
+            if let Some(connection) = self.connect_to(url).await {
+                return Some(connection);
+            }
+        }
+        None
+    }
+
+    // A connection to one of the URLs listed before `current`, if any of them works now
+    async fn failback(&self, current: &str) -> Option<Connection> {
+        for url in self.urls.iter().take_while(|url| *url != current) {
+            if let Some(connection) = self.connect_to(url).await {
+                return Some(connection);
+            }
+        }
+        None
+    }
+
+    async fn connect_to(&self, url: &str) -> Option<Connection> {
+        let client = match Client::new(url, self.auth_token.as_deref()).await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!(url, "Failed to connect to Celestia node: {}", e);
+                return None;
+            }
+        };
+        // Connecting can succeed even if the node can't serve anything yet
+        match probe(&client).await {
+            Ok(head) if head.header.chain_id.as_str() == self.chain_id => {
+                return Some(Connection { url: url.to_string(), client: Arc::new(client) });
+            }
+            Ok(head) => warn!(url, "Celestia node is on {}, expected {}", head.header.chain_id, self.chain_id),
+            Err(e) => warn!(url, "Celestia node isn't responding: {}", e),
+        }
+        None
+    }
+}
+
+async fn probe(client: &dyn CelestiaNode) -> Result<ExtendedHeader, NodeError> {
+    timeout(PROBE_TIMEOUT, client.local_head())
+        .await
+        .map_err(|_| NodeError::Unavailable("Timed out".to_string()))?
+}
+
+// The networks we serve, one node client each. Requests that don't name one get the first.
+pub struct Networks {
+    nodes: Vec<Arc<NodeClient>>,
+}
+
+impl Networks {
+    // There must be at least one, clap makes sure of that
+    pub fn new(nodes: Vec<Arc<NodeClient>>) -> Self {
+        assert!(!nodes.is_empty(), "No Celestia networks configured");
+        Networks { nodes }
+    }
+
+    pub fn default_chain_id(&self) -> &str {
+        self.nodes[0].chain_id()
+    }
+
+    pub fn node(&self, chain_id: &str) -> Option<&Arc<NodeClient>> {
+        self.nodes.iter().find(|node| node.chain_id() == chain_id)
+    }
+
+    pub fn nodes(&self) -> &[Arc<NodeClient>] {
+        &self.nodes
+    }
+}
+
+// Parses a --network value, CHAIN_ID=URL[,URL...]
+pub fn parse_network(value: &str) -> Result<(String, Vec<String>), String> {
+    let (chain_id, urls) = value.split_once('=')
+        .ok_or_else(|| "Expected CHAIN_ID=URL[,URL...]".to_string())?;
+    let urls: Vec<String> = urls.split(',')
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect();
+    if chain_id.is_empty() || urls.is_empty() {
+        return Err("Expected CHAIN_ID=URL[,URL...]".to_string());
+    }
+    Ok((chain_id.to_string(), urls))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CHAIN_ID;
+
+    #[test]
+    fn classifies_rpc_errors() {
+        let unavailable = [
+            ClientError::RequestTimeout,
+            ClientError::Transport("connection refused".into()),
+        ];
+        for e in unavailable {
+            assert!(matches!(NodeError::from(e), NodeError::Unavailable(_)));
+        }
+        assert!(matches!(NodeError::from(ClientError::Custom("blob: not found".to_string())), NodeError::Failed(_)));
+    }
+
+    #[test]
+    fn context_keeps_the_kind_of_error() {
+        let error = NodeError::Unavailable("Timed out".to_string()).context("Failed to get blob");
+        assert!(matches!(&error, NodeError::Unavailable(e) if e == "Failed to get blob: Timed out"));
+        let error = NodeError::Failed("Not found".to_string()).context("Failed to get blob");
+        assert!(matches!(&error, NodeError::Failed(e) if e == "Failed to get blob: Not found"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unavailable_until_connected() {
+        let node = NodeClient::new(CHAIN_ID.to_string(), Vec::new(), None, None);
+        assert!(matches!(node.client().await, Err(NodeError::Unavailable(_))));
+        assert!(node.check().await.is_err());
+    }
+}
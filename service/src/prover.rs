@@ -0,0 +1,84 @@
+use std::time::Duration;
+use alloy_primitives::B256;
+use sp1_sdk::network::proto::network::{prover_network_client::ProverNetworkClient, GetProofRequestDetailsRequest};
+use sp1_sdk::{NetworkProver, SP1ProofWithPublicValues, SP1ProvingKey, SP1Stdin};
+use tonic::transport::{ClientTlsConfig, Endpoint};
+use tracing::{info, info_span, Instrument};
+
+use crate::eqs::ProofMode;
+
+const DETAILS_TIMEOUT: Duration = Duration::from_secs(30);
+
+// What the worker needs from wherever proofs are made, the Succinct prover network outside of tests
+#[tonic::async_trait]
+pub trait ProverNetwork: Send + Sync {
+    // Returns the request ID once the network has accepted the request
+    async fn request_proof(&self, stdin: &SP1Stdin, proof_mode: ProofMode) -> Result<String, String>;
+
+    async fn wait_proof(&self, request_id: &str) -> Result<SP1ProofWithPublicValues, String>;
+
+    // How many cycles the network's execution of the program took, None if it hasn't said
+    async fn cycles(&self, request_id: &str) -> Result<Option<u64>, String>;
+}
+
+pub struct SuccinctNetwork {
+    prover: NetworkProver,
+    pk: SP1ProvingKey,
+    // The same RPC the prover talks to, for what NetworkProver doesn't expose
+    rpc_url: String,
+}
+
+impl SuccinctNetwork {
+    pub fn new(prover: NetworkProver, pk: SP1ProvingKey, rpc_url: String) -> Self {
+        SuccinctNetwork { prover, pk, rpc_url }
+    }
+}
+
+#[tonic::async_trait]
+impl ProverNetwork for SuccinctNetwork {
+    async fn request_proof(&self, stdin: &SP1Stdin, proof_mode: ProofMode) -> Result<String, String> {
+        let prove = self.prover.prove(&self.pk, stdin);
+        let prove = match proof_mode {
+            ProofMode::Groth16 => prove.groth16(),
+            ProofMode::Plonk => prove.plonk(),
+            ProofMode::Compressed => prove.compressed(),
+            ProofMode::Core => prove.core(),
+        };
+        let request_id = prove
+            .request_async()
+            .instrument(info_span!("prover.request"))
+            .await
+            .map_err(|e| format!("Failed to request proof: {}", e))?
+            .to_string();
+        info!(request_id = request_id.as_str(), "Requested proof");
+        Ok(request_id)
+    }
+
+    async fn wait_proof(&self, request_id: &str) -> Result<SP1ProofWithPublicValues, String> {
+        let request_id: B256 = request_id.parse()
+            .map_err(|e| format!("Invalid prover request ID {}: {}", request_id, e))?;
+        self.prover.wait_proof(request_id, None)
+            .instrument(info_span!("prover.wait_proof", request_id = %request_id))
+            .await
+            .map_err(|e| format!("Failed to get proof: {}", e))
+    }
+
+    async fn cycles(&self, request_id: &str) -> Result<Option<u64>, String> {
+        let request_id: B256 = request_id.parse()
+            .map_err(|e| format!("Invalid prover request ID {}: {}", request_id, e))?;
+        let mut endpoint = Endpoint::new(self.rpc_url.clone())
+            .map_err(|e| e.to_string())?
+            .timeout(DETAILS_TIMEOUT);
+        if self.rpc_url.starts_with("https://") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new().with_enabled_roots())
+                .map_err(|e| e.to_string())?;
+        }
+        let channel = endpoint.connect().await.map_err(|e| e.to_string())?;
+        let details = ProverNetworkClient::new(channel)
+            .get_proof_request_details(GetProofRequestDetailsRequest { request_id: request_id.to_vec() })
+            .instrument(info_span!("prover.request_details", request_id = %request_id))
+            .await
+            .map_err(|e| format!("Failed to get request details: {}", e.message()))?;
+        Ok(details.into_inner().request.and_then(|request| request.cycles))
+    }
+}
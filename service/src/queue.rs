@@ -0,0 +1,351 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::eqs::Priority;
+use crate::job::Job;
+use crate::storage;
+
+// Lanes in the order they're served
+const LANES: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+pub fn lane(priority: Priority) -> usize {
+    match priority {
+        Priority::High => 0,
+        Priority::Normal => 1,
+        Priority::Low => 2,
+    }
+}
+
+// Jobs waiting for the worker, in a lane per priority. A lane is only served once every
+// higher priority lane is empty or at its concurrency cap. Within a lane namespaces take turns
+// so one busy namespace can't starve the others, and within a namespace jobs are started in
+// the order they came in.
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+    limits: QueueLimits,
+    notify: Notify,
+}
+
+pub struct QueueLimits {
+    // Only limits jobs submitted through reserve, see push
+    pub capacity: usize,
+    // How many jobs can be running at once
+    pub running: usize,
+    // How many jobs of a priority can be running at once, on top of the overall limit
+    pub running_per_priority: HashMap<Priority, usize>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    lanes: [Lane; LANES.len()],
+    // Running jobs per lane
+    running: [usize; LANES.len()],
+    // Slots handed out by reserve that haven't been used or dropped yet
+    reserved: usize,
+}
+
+impl QueueState {
+    fn len(&self) -> usize {
+        self.lanes.iter().map(|lane| lane.len).sum()
+    }
+
+    fn find(&self, key: &[u8]) -> Option<usize> {
+        self.lanes.iter().position(|lane| lane.position(key).is_some())
+    }
+}
+
+#[derive(Default)]
+struct Lane {
+    // Namespaces with waiting jobs, in the order they'll be served
+    turns: VecDeque<Vec<u8>>,
+    // Job keys and jobs by namespace
+    waiting: HashMap<Vec<u8>, VecDeque<(Vec<u8>, Job)>>,
+    len: usize,
+}
+
+impl Lane {
+    fn push(&mut self, key: Vec<u8>, job: Job) {
+        let namespace_jobs = self.waiting.entry(job.namespace.clone()).or_insert_with(|| {
+            self.turns.push_back(job.namespace.clone());
+            VecDeque::new()
+        });
+        namespace_jobs.push_back((key, job));
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<(Vec<u8>, Job)> {
+        let namespace = self.turns.pop_front()?;
+        let namespace_jobs = self.waiting.get_mut(&namespace)?;
+        let entry = namespace_jobs.pop_front();
+        if namespace_jobs.is_empty() {
+            self.waiting.remove(&namespace);
+        } else {
+            self.turns.push_back(namespace);
+        }
+        self.len -= 1;
+        entry
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<(Vec<u8>, Job)> {
+        let (namespace, index) = self.waiting.iter().find_map(|(namespace, jobs)| {
+            jobs.iter().position(|(job_key, _)| job_key == key).map(|index| (namespace.clone(), index))
+        })?;
+
+        let namespace_jobs = self.waiting.get_mut(&namespace)?;
+        let entry = namespace_jobs.remove(index);
+        if namespace_jobs.is_empty() {
+            self.waiting.remove(&namespace);
+            self.turns.retain(|turn| *turn != namespace);
+        }
+        self.len -= 1;
+        entry
+    }
+
+    // How many jobs in this lane will be started before this one
+    fn position(&self, key: &[u8]) -> Option<usize> {
+        let (turn, index) = self.turns.iter().enumerate().find_map(|(turn, namespace)| {
+            self.waiting[namespace].iter()
+                .position(|(job_key, _)| job_key == key)
+                .map(|index| (turn, index))
+        })?;
+
+        // Every namespace gets a job started per round, our job is started in round `index`
+        Some(self.turns.iter().enumerate().map(|(other_turn, namespace)| {
+            let rounds = if other_turn < turn { index + 1 } else { index };
+            self.waiting[namespace].len().min(rounds)
+        }).sum())
+    }
+}
+
+#[derive(Debug)]
+pub struct QueueFull;
+
+// Room for one more job in the queue, given back when dropped
+pub struct QueueSlot<'a> {
+    queue: &'a JobQueue,
+}
+
+impl QueueSlot<'_> {
+    pub fn push(self, job: Job, priority: Priority) {
+        self.queue.push(job, priority);
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().reserved -= 1;
+    }
+}
+
+// A job taken off the queue, it counts against the concurrency limits until dropped
+pub struct Running {
+    queue: Arc<JobQueue>,
+    lane: usize,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().running[self.lane] -= 1;
+        self.queue.notify.notify_one();
+    }
+}
+
+impl JobQueue {
+    pub fn new(limits: QueueLimits) -> Self {
+        JobQueue { state: Mutex::new(QueueState::default()), limits, notify: Notify::new() }
+    }
+
+    // Makes sure there's room for a job before the caller commits to queueing it
+    pub fn reserve(&self) -> Result<QueueSlot<'_>, QueueFull> {
+        let mut state = self.state.lock().unwrap();
+        if state.len() + state.reserved >= self.limits.capacity {
+            return Err(QueueFull);
+        }
+        state.reserved += 1;
+        Ok(QueueSlot { queue: self })
+    }
+
+    // Queues the job even if the queue is full, for jobs we've already accepted
+    // (e.g. recovered after a restart or retried by an operator)
+    pub fn push(&self, job: Job, priority: Priority) {
+        let key = storage::job_key(&job);
+        self.state.lock().unwrap().lanes[lane(priority)].push(key, job);
+        self.notify.notify_one();
+    }
+
+    // Waits until a job can be started, returning it with its key
+    pub async fn pop(self: &Arc<Self>) -> (Vec<u8>, Job, Running) {
+        loop {
+            let notified = self.notify.notified();
+            if let Some((key, job, lane)) = self.try_pop() {
+                return (key, job, Running { queue: self.clone(), lane });
+            }
+            notified.await;
+        }
+    }
+
+    fn try_pop(&self) -> Option<(Vec<u8>, Job, usize)> {
+        let mut state = self.state.lock().unwrap();
+        if state.running.iter().sum::<usize>() >= self.limits.running {
+            return None;
+        }
+        let lane = (0..LANES.len()).find(|&lane| {
+            let cap = self.limits.running_per_priority.get(&LANES[lane]).copied().unwrap_or(usize::MAX);
+            state.lanes[lane].len > 0 && state.running[lane] < cap
+        })?;
+        let (key, job) = state.lanes[lane].pop()?;
+        state.running[lane] += 1;
+        Some((key, job, lane))
+    }
+
+    // Takes the job out of the queue, returning whether it was there
+    pub fn remove(&self, key: &[u8]) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.lanes.iter_mut().any(|lane| lane.remove(key).is_some())
+    }
+
+    // Moves a queued job to a higher priority lane, returning whether it was moved
+    pub fn promote(&self, key: &[u8], priority: Priority) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(current) = state.find(key) else {
+            return false;
+        };
+        if current <= lane(priority) {
+            return false;
+        }
+        let Some((key, job)) = state.lanes[current].remove(key) else {
+            return false;
+        };
+        state.lanes[lane(priority)].push(key, job);
+        drop(state);
+        self.notify.notify_one();
+        true
+    }
+
+    // How many jobs will be started before this one, if it's queued. Everything in the
+    // higher priority lanes goes first, even if those are at their cap right now.
+    pub fn position(&self, key: &[u8]) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        let current = state.find(key)?;
+        let ahead: usize = state.lanes[..current].iter().map(|lane| lane.len).sum();
+        Some(ahead + state.lanes[current].position(key)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    // A job at `height` in a namespace of its own per `namespace`
+    fn job(height: u64, namespace: u8) -> Job {
+        let mut job = test_utils::job(height);
+        *job.namespace.last_mut().unwrap() = namespace;
+        job
+    }
+
+    fn unlimited(capacity: usize) -> JobQueue {
+        JobQueue::new(QueueLimits { capacity, running: usize::MAX, running_per_priority: HashMap::new() })
+    }
+
+    // The order the queued jobs are started in
+    fn heights(queue: &JobQueue) -> Vec<u64> {
+        std::iter::from_fn(|| queue.try_pop().map(|(_, job, _)| job.height)).collect()
+    }
+
+    #[test]
+    fn namespaces_take_turns() {
+        let queue = unlimited(10);
+        for height in 0..3 {
+            queue.push(job(height, 1), Priority::Normal);
+        }
+        queue.push(job(10, 2), Priority::Normal);
+        queue.push(job(11, 2), Priority::Normal);
+        let key = storage::job_key(&job(11, 2));
+        assert_eq!(queue.position(&key), Some(3));
+        assert_eq!(heights(&queue), [0, 10, 1, 11, 2]);
+    }
+
+    #[test]
+    fn serves_higher_priorities_first() {
+        let queue = unlimited(10);
+        queue.push(job(1, 1), Priority::Low);
+        queue.push(job(2, 1), Priority::Normal);
+        queue.push(job(3, 1), Priority::High);
+        queue.push(job(4, 1), Priority::Normal);
+        assert_eq!(heights(&queue), [3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn caps_running_jobs_per_priority() {
+        let queue = JobQueue::new(QueueLimits {
+            capacity: 10,
+            running: usize::MAX,
+            running_per_priority: HashMap::from([(Priority::High, 1)]),
+        });
+        queue.push(job(1, 1), Priority::High);
+        queue.push(job(2, 1), Priority::High);
+        queue.push(job(3, 1), Priority::Normal);
+        // The second high priority job waits for the first, normal ones go ahead meanwhile
+        assert_eq!(heights(&queue), [1, 3]);
+    }
+
+    #[test]
+    fn promoted_jobs_move_ahead() {
+        let queue = unlimited(10);
+        for height in 0..3 {
+            queue.push(job(height, 1), Priority::Normal);
+        }
+        queue.push(job(10, 2), Priority::Low);
+        let key = storage::job_key(&job(10, 2));
+        assert_eq!(queue.position(&key), Some(3));
+
+        assert!(queue.promote(&key, Priority::High));
+        assert_eq!(queue.position(&key), Some(0));
+        assert_eq!(queue.position(&storage::job_key(&job(0, 1))), Some(1));
+        // Never demoted
+        assert!(!queue.promote(&key, Priority::Low));
+        assert_eq!(heights(&queue), [10, 0, 1, 2]);
+    }
+
+    #[test]
+    fn turns_away_jobs_when_full() {
+        let queue = test_utils::queue(2);
+        queue.reserve().unwrap().push(job(1, 1), Priority::Normal);
+        let slot = queue.reserve().unwrap();
+        assert!(queue.reserve().is_err());
+        // Accepted jobs are queued regardless
+        queue.push(job(2, 1), Priority::Normal);
+        assert_eq!(queue.len(), 2);
+        drop(slot);
+        assert!(queue.reserve().is_err());
+    }
+
+    #[test]
+    fn dropping_a_slot_gives_it_back() {
+        let queue = test_utils::queue(1);
+        let slot = queue.reserve().unwrap();
+        assert!(queue.reserve().is_err());
+        drop(slot);
+        assert!(queue.reserve().is_ok());
+    }
+
+    #[tokio::test]
+    async fn finished_jobs_make_room_to_start_more() {
+        let queue = Arc::new(JobQueue::new(QueueLimits { capacity: 10, running: 1, running_per_priority: HashMap::new() }));
+        queue.push(job(1, 1), Priority::Normal);
+        queue.push(job(2, 1), Priority::Normal);
+
+        let (_, first, running) = queue.pop().await;
+        assert_eq!(first.height, 1);
+        assert!(queue.try_pop().is_none());
+        drop(running);
+        let (_, second, _running) = queue.pop().await;
+        assert_eq!(second.height, 2);
+    }
+}
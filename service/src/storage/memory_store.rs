@@ -0,0 +1,146 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::Mutex;
+use sp1_sdk::SP1ProofWithPublicValues;
+
+use eq_common::KeccakInclusionToDataRootProofInput;
+use crate::job::JobRecord;
+use crate::light_client::Checkpoint;
+use super::{decode_job_key, JobIter, JobStore, StorageError};
+
+// Keeps everything in memory, for tests and deployments that don't need jobs to survive a restart.
+// Records are stored encoded the same way as in sled so both stores behave the same.
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    jobs: BTreeMap<Vec<u8>, Vec<u8>>,
+    proofs: HashMap<Vec<u8>, Vec<u8>>,
+    inputs: HashMap<Vec<u8>, Vec<u8>>,
+    job_ids: HashMap<String, Vec<u8>>,
+    trusted_headers: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl JobStore for MemoryStore {
+    fn get_job(&self, key: &[u8]) -> Result<Option<JobRecord>, StorageError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.jobs.get(key)
+            .map(|record| bincode::deserialize(record))
+            .transpose()?)
+    }
+
+    fn put_job(&self, key: &[u8], record: &JobRecord) -> Result<(), StorageError> {
+        let record = bincode::serialize(record)?;
+        self.inner.lock().unwrap().jobs.insert(key.to_vec(), record);
+        Ok(())
+    }
+
+    fn insert_job(&self, key: &[u8], record: &JobRecord) -> Result<Option<JobRecord>, StorageError> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(existing) = inner.jobs.get(key) {
+            return Ok(Some(bincode::deserialize(existing)?));
+        }
+        inner.jobs.insert(key.to_vec(), bincode::serialize(record)?);
+        inner.job_ids.insert(record.job_id.clone(), key.to_vec());
+        Ok(None)
+    }
+
+    fn replace_job(&self, key: &[u8], current: &JobRecord, new: &JobRecord) -> Result<bool, StorageError> {
+        let current = bincode::serialize(current)?;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.jobs.get(key) != Some(&current) {
+            return Ok(false);
+        }
+        inner.jobs.insert(key.to_vec(), bincode::serialize(new)?);
+        Ok(true)
+    }
+
+    fn job_key_by_id(&self, job_id: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.inner.lock().unwrap().job_ids.get(job_id).cloned())
+    }
+
+    fn jobs_from(&self, start: Bound<Vec<u8>>) -> JobIter<'_> {
+        // Copy the range out so we don't hold the lock while the caller iterates
+        let jobs: Vec<(Vec<u8>, Vec<u8>)> = self.inner.lock().unwrap()
+            .jobs
+            .range((start, Bound::Unbounded))
+            .map(|(key, record)| (key.clone(), record.clone()))
+            .collect();
+        Box::new(jobs.into_iter().map(|(key, record)| {
+            let job = decode_job_key(&key)?;
+            Ok((key, job, bincode::deserialize(&record)?))
+        }))
+    }
+
+    fn get_proof(&self, key: &[u8]) -> Result<Option<SP1ProofWithPublicValues>, StorageError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.proofs.get(key)
+            .map(|proof| bincode::deserialize(proof))
+            .transpose()?)
+    }
+
+    fn put_proof(&self, key: &[u8], proof: &SP1ProofWithPublicValues) -> Result<(), StorageError> {
+        let proof = bincode::serialize(proof)?;
+        self.inner.lock().unwrap().proofs.insert(key.to_vec(), proof);
+        Ok(())
+    }
+
+    fn proof_size(&self, key: &[u8]) -> Result<Option<u64>, StorageError> {
+        Ok(self.inner.lock().unwrap().proofs.get(key).map(|proof| proof.len() as u64))
+    }
+
+    fn remove_proof(&self, key: &[u8]) -> Result<(), StorageError> {
+        self.inner.lock().unwrap().proofs.remove(key);
+        Ok(())
+    }
+
+    fn get_input(&self, key: &[u8]) -> Result<Option<KeccakInclusionToDataRootProofInput>, StorageError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.inputs.get(key)
+            .map(|input| bincode::deserialize(input))
+            .transpose()?)
+    }
+
+    fn put_input(&self, key: &[u8], input: &KeccakInclusionToDataRootProofInput) -> Result<(), StorageError> {
+        let input = bincode::serialize(input)?;
+        self.inner.lock().unwrap().inputs.insert(key.to_vec(), input);
+        Ok(())
+    }
+
+    fn get_trusted_header(&self, chain_id: &str) -> Result<Option<Checkpoint>, StorageError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.trusted_headers.get(chain_id)
+            .map(|header| bincode::deserialize(header))
+            .transpose()?)
+    }
+
+    fn put_trusted_header(&self, chain_id: &str, header: &Checkpoint) -> Result<(), StorageError> {
+        let header = bincode::serialize(header)?;
+        self.inner.lock().unwrap().trusted_headers.insert(chain_id.to_string(), header);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn size_on_disk(&self) -> Result<u64, StorageError> {
+        let inner = self.inner.lock().unwrap();
+        let size = inner.jobs.iter().map(|(key, value)| key.len() + value.len())
+            .chain(inner.proofs.iter().map(|(key, value)| key.len() + value.len()))
+            .chain(inner.inputs.iter().map(|(key, value)| key.len() + value.len()))
+            .chain(inner.job_ids.iter().map(|(id, key)| id.len() + key.len()))
+            .chain(inner.trusted_headers.iter().map(|(chain_id, header)| chain_id.len() + header.len()))
+            .sum::<usize>();
+        Ok(size as u64)
+    }
+}
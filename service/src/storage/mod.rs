@@ -0,0 +1,236 @@
+use std::ops::Bound;
+use celestia_types::nmt::NS_SIZE;
+use sp1_sdk::SP1ProofWithPublicValues;
+use thiserror::Error;
+use tonic::Status;
+
+use eq_common::KeccakInclusionToDataRootProofInput;
+use crate::eqs::ProofMode;
+use crate::job::{Job, JobRecord};
+use crate::light_client::Checkpoint;
+
+mod sled_store;
+pub use sled_store::SledStore;
+mod memory_store;
+pub use memory_store::MemoryStore;
+
+// Job keys are this prefix followed by the height (big endian, so jobs sort by height),
+// namespace, commitment, proof mode and, taking up the rest of the key, the chain ID
+const JOB_KEY_PREFIX: &[u8] = b"job/";
+const COMMITMENT_SIZE: usize = 32;
+// Everything but the chain ID
+const JOB_KEY_FIXED_SIZE: usize = JOB_KEY_PREFIX.len() + 8 + NS_SIZE + COMMITMENT_SIZE + 1;
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Database error: {0}")]
+    Db(#[from] sled::Error),
+
+    #[error("Failed to encode or decode record: {0}")]
+    Codec(#[from] bincode::Error),
+
+    #[error("Invalid job key")]
+    InvalidKey,
+
+    #[error("Database schema version {0} is newer than the latest supported version {1}")]
+    UnsupportedSchema(u64, u64),
+
+    #[error("Database has no schema version, it wasn't created by this service")]
+    UnknownSchema,
+
+    #[error("Database has jobs from before there could be more than one network, set --legacy-chain-id to the chain ID they were for")]
+    LegacyChainIdRequired,
+}
+
+impl From<StorageError> for Status {
+    fn from(e: StorageError) -> Self {
+        Status::internal(e.to_string())
+    }
+}
+
+pub type JobIter<'a> = Box<dyn Iterator<Item = Result<(Vec<u8>, Job, JobRecord), StorageError>> + Send + 'a>;
+
+// Everything the service keeps about jobs, keyed by job key (see job_key) unless noted
+pub trait JobStore: Send + Sync {
+    fn get_job(&self, key: &[u8]) -> Result<Option<JobRecord>, StorageError>;
+
+    fn put_job(&self, key: &[u8], record: &JobRecord) -> Result<(), StorageError>;
+
+    // Inserts the job unless it already exists, in which case the existing record is returned.
+    // This must be atomic so concurrent identical requests only ever create one job.
+    fn insert_job(&self, key: &[u8], record: &JobRecord) -> Result<Option<JobRecord>, StorageError>;
+
+    // Replaces the record only if it hasn't changed from `current`, returning whether it did
+    fn replace_job(&self, key: &[u8], current: &JobRecord, new: &JobRecord) -> Result<bool, StorageError>;
+
+    fn job_key_by_id(&self, job_id: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    // Jobs in key order (i.e. by height), starting from the given bound
+    fn jobs_from(&self, start: Bound<Vec<u8>>) -> JobIter<'_>;
+
+    fn get_proof(&self, key: &[u8]) -> Result<Option<SP1ProofWithPublicValues>, StorageError>;
+
+    fn put_proof(&self, key: &[u8], proof: &SP1ProofWithPublicValues) -> Result<(), StorageError>;
+
+    // Size of the stored proof in bytes, without decoding it
+    fn proof_size(&self, key: &[u8]) -> Result<Option<u64>, StorageError>;
+
+    fn remove_proof(&self, key: &[u8]) -> Result<(), StorageError>;
+
+    // The zkVM input the proof was requested with
+    fn get_input(&self, key: &[u8]) -> Result<Option<KeccakInclusionToDataRootProofInput>, StorageError>;
+
+    fn put_input(&self, key: &[u8], input: &KeccakInclusionToDataRootProofInput) -> Result<(), StorageError>;
+
+    // The newest header the light client has verified on the network, so it can pick up from
+    // there after a restart
+    fn get_trusted_header(&self, chain_id: &str) -> Result<Option<Checkpoint>, StorageError>;
+
+    fn put_trusted_header(&self, chain_id: &str, header: &Checkpoint) -> Result<(), StorageError>;
+
+    // Makes sure everything written so far is durable
+    fn flush(&self) -> Result<(), StorageError>;
+
+    fn size_on_disk(&self) -> Result<u64, StorageError>;
+}
+
+pub fn job_key(job: &Job) -> Vec<u8> {
+    let mut key = Vec::with_capacity(JOB_KEY_FIXED_SIZE + job.chain_id.len());
+    key.extend_from_slice(JOB_KEY_PREFIX);
+    key.extend_from_slice(&job.height.to_be_bytes());
+    key.extend_from_slice(&job.namespace);
+    key.extend_from_slice(&job.commitment);
+    key.push(job.proof_mode as u8);
+    key.extend_from_slice(job.chain_id.as_bytes());
+    key
+}
+
+pub fn decode_job_key(key: &[u8]) -> Result<Job, StorageError> {
+    if key.len() <= JOB_KEY_FIXED_SIZE || !key.starts_with(JOB_KEY_PREFIX) {
+        return Err(StorageError::InvalidKey);
+    }
+    let (height, rest) = key[JOB_KEY_PREFIX.len()..].split_at(8);
+    let (namespace, rest) = rest.split_at(NS_SIZE);
+    let (commitment, rest) = rest.split_at(COMMITMENT_SIZE);
+    let (proof_mode, chain_id) = rest.split_at(1);
+    Ok(Job {
+        height: u64::from_be_bytes(height.try_into().map_err(|_| StorageError::InvalidKey)?),
+        namespace: namespace.to_vec(),
+        commitment: commitment.to_vec(),
+        proof_mode: ProofMode::try_from(proof_mode[0] as i32).map_err(|_| StorageError::InvalidKey)?,
+        chain_id: String::from_utf8(chain_id.to_vec()).map_err(|_| StorageError::InvalidKey)?,
+    })
+}
+
+// The smallest key of any job at this height
+pub fn height_key(height: u64) -> Vec<u8> {
+    [JOB_KEY_PREFIX, &height.to_be_bytes()].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eqs::Priority;
+    use crate::job::JobStatus;
+    use crate::test_utils::{self, CHAIN_ID};
+    use tendermint::Hash;
+
+    #[test]
+    fn job_key_round_trip() {
+        let job = test_utils::job(42);
+        let decoded = decode_job_key(&job_key(&job)).unwrap();
+        assert_eq!(decoded.height, job.height);
+        assert_eq!(decoded.namespace, job.namespace);
+        assert_eq!(decoded.commitment, job.commitment);
+        assert_eq!(decoded.proof_mode, job.proof_mode);
+        assert_eq!(decoded.chain_id, job.chain_id);
+    }
+
+    #[test]
+    fn decode_job_key_rejects_invalid_keys() {
+        let key = job_key(&test_utils::job(42));
+        // Without a chain ID
+        assert!(decode_job_key(&key[..JOB_KEY_FIXED_SIZE]).is_err());
+        let mut wrong_prefix = key.clone();
+        wrong_prefix[0] = b'x';
+        assert!(decode_job_key(&wrong_prefix).is_err());
+        let mut wrong_proof_mode = key.clone();
+        wrong_proof_mode[JOB_KEY_FIXED_SIZE - 1] = 99;
+        assert!(decode_job_key(&wrong_proof_mode).is_err());
+    }
+
+    #[test]
+    fn job_keys_sort_by_height() {
+        let low = job_key(&test_utils::job(9));
+        let high = job_key(&test_utils::job(256));
+        assert!(low < high);
+        assert!(height_key(256) <= high);
+        assert!(height_key(257) > high);
+    }
+
+    // What every JobStore has to do, run against each of them
+    pub fn check_store(store: &dyn JobStore) {
+        let job = test_utils::job(1);
+        let key = job_key(&job);
+        let record = JobRecord::queued(&key, Priority::Normal);
+
+        // Only the first insert creates the job
+        assert!(store.insert_job(&key, &record).unwrap().is_none());
+        let mut other = JobRecord::queued(&key, Priority::High);
+        other.job_id = "other".to_string();
+        let existing = store.insert_job(&key, &other).unwrap().unwrap();
+        assert_eq!(existing.job_id, record.job_id);
+        assert_eq!(store.job_key_by_id(&record.job_id).unwrap(), Some(key.clone()));
+        assert_eq!(store.job_key_by_id("other").unwrap(), None);
+
+        // Replacing only works against the current record
+        let mut fetching = record.clone();
+        fetching.update(JobStatus::Fetching);
+        assert!(store.replace_job(&key, &record, &fetching).unwrap());
+        let mut failed = record.clone();
+        failed.update(JobStatus::Failed("stale".to_string()));
+        assert!(!store.replace_job(&key, &record, &failed).unwrap());
+        assert!(matches!(store.get_job(&key).unwrap().unwrap().status, JobStatus::Fetching));
+
+        store.put_input(&key, &test_utils::input()).unwrap();
+        assert_eq!(store.get_input(&key).unwrap().unwrap().blob_data, test_utils::input().blob_data);
+
+        // Kept per network
+        assert!(store.get_trusted_header(CHAIN_ID).unwrap().is_none());
+        let header = Checkpoint { height: 100, hash: Hash::Sha256([9; 32]) };
+        store.put_trusted_header(CHAIN_ID, &header).unwrap();
+        let stored = store.get_trusted_header(CHAIN_ID).unwrap().unwrap();
+        assert_eq!((stored.height, stored.hash), (header.height, header.hash));
+        assert!(store.get_trusted_header("other-1").unwrap().is_none());
+
+        let proof = test_utils::proof();
+        store.put_proof(&key, &proof).unwrap();
+        assert_eq!(store.proof_size(&key).unwrap(), Some(bincode::serialize(&proof).unwrap().len() as u64));
+        assert_eq!(store.get_proof(&key).unwrap().unwrap().public_values.as_slice(), proof.public_values.as_slice());
+        store.remove_proof(&key).unwrap();
+        assert!(store.get_proof(&key).unwrap().is_none());
+
+        // Jobs come back in height order, from the given height on
+        for height in [5, 3, 4] {
+            let key = job_key(&test_utils::job(height));
+            store.put_job(&key, &JobRecord::queued(&key, Priority::Low)).unwrap();
+        }
+        let heights: Vec<u64> = store.jobs_from(Bound::Included(height_key(3)))
+            .map(|entry| entry.unwrap().1.height)
+            .collect();
+        assert_eq!(heights, [3, 4, 5]);
+    }
+
+    #[test]
+    fn memory_store() {
+        check_store(&MemoryStore::new());
+    }
+
+    #[test]
+    fn memory_store_size_counts_trusted_headers() {
+        let store = MemoryStore::new();
+        assert_eq!(store.size_on_disk().unwrap(), 0);
+        store.put_trusted_header(CHAIN_ID, &Checkpoint { height: 100, hash: Hash::Sha256([9; 32]) }).unwrap();
+        assert!(store.size_on_disk().unwrap() > 0);
+    }
+}
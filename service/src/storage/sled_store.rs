@@ -0,0 +1,457 @@
+use std::ops::Bound;
+use std::path::Path;
+use sled::transaction::{ConflictableTransactionError, TransactionError, Transactional};
+use sp1_sdk::SP1ProofWithPublicValues;
+
+use eq_common::KeccakInclusionToDataRootProofInput;
+use crate::eqs::Priority;
+use crate::job::JobRecord;
+use crate::light_client::Checkpoint;
+use super::{decode_job_key, JobIter, JobStore, StorageError};
+
+/*
+    Layout of the DB, every tree is keyed by job key unless noted:
+    1. jobs: JobRecord
+    2. proofs: SP1ProofWithPublicValues of completed jobs
+    3. inputs: KeccakInclusionToDataRootProofInput the proof was requested with
+    4. job_ids: job ID -> job key
+    5. metadata: anything about the DB itself, keyed by name, plus the newest header the light
+       client has verified on each network under trusted_header/<chain ID>
+
+    Any change to existing trees or to the records stored in them needs a new entry in MIGRATIONS.
+*/
+
+const JOBS_TREE: &str = "jobs";
+const PROOFS_TREE: &str = "proofs";
+const INPUTS_TREE: &str = "inputs";
+const JOB_IDS_TREE: &str = "job_ids";
+const METADATA_TREE: &str = "metadata";
+
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+const TRUSTED_HEADER_PREFIX: &[u8] = b"trusted_header/";
+
+// MIGRATIONS[n] upgrades a DB at schema version n + 1 to version n + 2, version 1 being the
+// first layout. They're given the chain ID the operator says jobs from before there could be
+// more than one network were for, if any.
+type Migration = fn(&SledStore, Option<&str>) -> Result<(), StorageError>;
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2, migrate_v2_to_v3];
+const LATEST_VERSION: u64 = MIGRATIONS.len() as u64 + 1;
+
+pub struct SledStore {
+    db: sled::Db,
+    jobs: sled::Tree,
+    proofs: sled::Tree,
+    inputs: sled::Tree,
+    job_ids: sled::Tree,
+    metadata: sled::Tree,
+}
+
+impl SledStore {
+    // Opens the DB, bringing it up to the latest schema version first if needed. Jobs stored
+    // before there could be more than one network can only be migrated given `legacy_chain_id`.
+    pub fn open<P: AsRef<Path>>(path: P, legacy_chain_id: Option<&str>) -> Result<Self, StorageError> {
+        Self::from_db(sled::open(path)?, legacy_chain_id)
+    }
+
+    fn from_db(db: sled::Db, legacy_chain_id: Option<&str>) -> Result<Self, StorageError> {
+        let store = SledStore {
+            jobs: db.open_tree(JOBS_TREE)?,
+            proofs: db.open_tree(PROOFS_TREE)?,
+            inputs: db.open_tree(INPUTS_TREE)?,
+            job_ids: db.open_tree(JOB_IDS_TREE)?,
+            metadata: db.open_tree(METADATA_TREE)?,
+            db,
+        };
+        store.migrate(legacy_chain_id)?;
+        Ok(store)
+    }
+
+    fn migrate(&self, legacy_chain_id: Option<&str>) -> Result<(), StorageError> {
+        let latest = LATEST_VERSION;
+        let mut version = match self.metadata.get(SCHEMA_VERSION_KEY)? {
+            Some(version) => bincode::deserialize(&version)?,
+            // Every version keeps the default tree empty, so this isn't one of ours
+            None if !self.db.is_empty() => return Err(StorageError::UnknownSchema),
+            None => latest,
+        };
+        if version > latest {
+            return Err(StorageError::UnsupportedSchema(version, latest));
+        }
+
+        while version < latest {
+            MIGRATIONS[version as usize - 1](self, legacy_chain_id)?;
+            version += 1;
+            self.metadata.insert(SCHEMA_VERSION_KEY, bincode::serialize(&version)?)?;
+            self.db.flush()?;
+        }
+        self.metadata.insert(SCHEMA_VERSION_KEY, bincode::serialize(&version)?)?;
+        Ok(())
+    }
+}
+
+impl JobStore for SledStore {
+    fn get_job(&self, key: &[u8]) -> Result<Option<JobRecord>, StorageError> {
+        Ok(self.jobs.get(key)?
+            .map(|record| bincode::deserialize(&record))
+            .transpose()?)
+    }
+
+    fn put_job(&self, key: &[u8], record: &JobRecord) -> Result<(), StorageError> {
+        self.jobs.insert(key, bincode::serialize(record)?)?;
+        Ok(())
+    }
+
+    fn insert_job(&self, key: &[u8], record: &JobRecord) -> Result<Option<JobRecord>, StorageError> {
+        let value = bincode::serialize(record)?;
+        // Along with its job ID, so there's never a job that can't be looked up by ID
+        let existing = (&self.jobs, &self.job_ids).transaction(|(jobs, job_ids)| {
+            if let Some(existing) = jobs.get(key)? {
+                return Ok(Some(existing));
+            }
+            jobs.insert(key, value.as_slice())?;
+            job_ids.insert(record.job_id.as_bytes(), key)?;
+            Ok::<_, ConflictableTransactionError>(None)
+        }).map_err(transaction_error)?;
+        Ok(existing
+            .map(|record| bincode::deserialize(&record))
+            .transpose()?)
+    }
+
+    fn replace_job(&self, key: &[u8], current: &JobRecord, new: &JobRecord) -> Result<bool, StorageError> {
+        let current = bincode::serialize(current)?;
+        let new = bincode::serialize(new)?;
+        Ok(self.jobs.compare_and_swap(key, Some(current), Some(new))?.is_ok())
+    }
+
+    fn job_key_by_id(&self, job_id: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.job_ids.get(job_id)?.map(|key| key.to_vec()))
+    }
+
+    fn jobs_from(&self, start: Bound<Vec<u8>>) -> JobIter<'_> {
+        Box::new(self.jobs.range((start, Bound::Unbounded)).map(|entry| {
+            let (key, record) = entry?;
+            let job = decode_job_key(&key)?;
+            Ok((key.to_vec(), job, bincode::deserialize(&record)?))
+        }))
+    }
+
+    fn get_proof(&self, key: &[u8]) -> Result<Option<SP1ProofWithPublicValues>, StorageError> {
+        Ok(self.proofs.get(key)?
+            .map(|proof| bincode::deserialize(&proof))
+            .transpose()?)
+    }
+
+    fn put_proof(&self, key: &[u8], proof: &SP1ProofWithPublicValues) -> Result<(), StorageError> {
+        self.proofs.insert(key, bincode::serialize(proof)?)?;
+        Ok(())
+    }
+
+    fn proof_size(&self, key: &[u8]) -> Result<Option<u64>, StorageError> {
+        Ok(self.proofs.get(key)?.map(|proof| proof.len() as u64))
+    }
+
+    fn remove_proof(&self, key: &[u8]) -> Result<(), StorageError> {
+        self.proofs.remove(key)?;
+        Ok(())
+    }
+
+    fn get_input(&self, key: &[u8]) -> Result<Option<KeccakInclusionToDataRootProofInput>, StorageError> {
+        Ok(self.inputs.get(key)?
+            .map(|input| bincode::deserialize(&input))
+            .transpose()?)
+    }
+
+    fn put_input(&self, key: &[u8], input: &KeccakInclusionToDataRootProofInput) -> Result<(), StorageError> {
+        self.inputs.insert(key, bincode::serialize(input)?)?;
+        Ok(())
+    }
+
+    fn get_trusted_header(&self, chain_id: &str) -> Result<Option<Checkpoint>, StorageError> {
+        Ok(self.metadata.get([TRUSTED_HEADER_PREFIX, chain_id.as_bytes()].concat())?
+            .map(|header| bincode::deserialize(&header))
+            .transpose()?)
+    }
+
+    fn put_trusted_header(&self, chain_id: &str, header: &Checkpoint) -> Result<(), StorageError> {
+        self.metadata.insert([TRUSTED_HEADER_PREFIX, chain_id.as_bytes()].concat(), bincode::serialize(header)?)?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), StorageError> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn size_on_disk(&self) -> Result<u64, StorageError> {
+        Ok(self.db.size_on_disk()?)
+    }
+}
+
+fn transaction_error(e: TransactionError) -> StorageError {
+    match e {
+        TransactionError::Abort(e) | TransactionError::Storage(e) => StorageError::Db(e),
+    }
+}
+
+// Version 2 added the priority to JobRecord, existing jobs get the default priority
+fn migrate_v1_to_v2(store: &SledStore, _legacy_chain_id: Option<&str>) -> Result<(), StorageError> {
+    let mut records = Vec::new();
+    for entry in store.jobs.iter() {
+        let (key, record) = entry?;
+        let record: v1::JobRecord = bincode::deserialize(&record)?;
+        records.push((key, bincode::serialize(&JobRecord {
+            job_id: record.job_id,
+            status: record.status,
+            priority: Priority::Normal,
+            proof_id: record.proof_id,
+            attempts: record.attempts,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        })?));
+    }
+    // Together with the version bump, since v2 records can't be told apart from v1 records
+    // if this is interrupted
+    let version = bincode::serialize(&2u64)?;
+    (&store.jobs, &store.metadata).transaction(|(jobs, metadata)| {
+        for (key, record) in &records {
+            jobs.insert(key, record.as_slice())?;
+        }
+        metadata.insert(SCHEMA_VERSION_KEY, version.as_slice())?;
+        Ok::<_, ConflictableTransactionError>(())
+    }).map_err(transaction_error)?;
+    Ok(())
+}
+
+// Version 3 added the chain ID to the end of job keys. Nothing in a version 2 DB says which
+// network its jobs were for, so that has to come from the operator.
+fn migrate_v2_to_v3(store: &SledStore, legacy_chain_id: Option<&str>) -> Result<(), StorageError> {
+    // Version 2 keys are shorter than any version 3 key, so this can be rerun if interrupted
+    let mut old_keys = Vec::new();
+    for key in store.jobs.iter().keys() {
+        let key = key?;
+        if key.len() == v1::JOB_KEY_SIZE {
+            old_keys.push(key);
+        }
+    }
+    if old_keys.is_empty() {
+        return Ok(());
+    }
+    let chain_id = legacy_chain_id.ok_or(StorageError::LegacyChainIdRequired)?;
+    for old_key in old_keys {
+        let mut key = old_key.to_vec();
+        key.extend_from_slice(chain_id.as_bytes());
+
+        // Only remove the old entries once the new ones are written
+        for tree in [&store.proofs, &store.inputs] {
+            if let Some(value) = tree.get(&old_key)? {
+                tree.insert(&key, value)?;
+            }
+        }
+        if let Some(value) = store.jobs.get(&old_key)? {
+            let record: JobRecord = bincode::deserialize(&value)?;
+            store.job_ids.insert(record.job_id.as_bytes(), key.as_slice())?;
+            store.jobs.insert(&key, value)?;
+        }
+        for tree in [&store.proofs, &store.inputs, &store.jobs] {
+            tree.remove(&old_key)?;
+        }
+    }
+    Ok(())
+}
+
+// Records as they were stored at schema version 1, these must never change
+mod v1 {
+    use celestia_types::nmt::NS_SIZE;
+    use serde::{Deserialize, Serialize};
+
+    // Only ever had variants added at the end, which doesn't change how existing ones are encoded
+    use crate::job::JobStatus;
+
+    // Job keys from version 1 until version 3: prefix, height, namespace, commitment and proof mode
+    pub const JOB_KEY_SIZE: usize = 4 + 8 + NS_SIZE + 32 + 1;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct JobRecord {
+        pub job_id: String,
+        pub status: JobStatus,
+        pub proof_id: Option<String>,
+        pub attempts: u32,
+        pub created_at: u64,
+        pub updated_at: u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::JobStatus;
+    use crate::storage::{job_key, tests::check_store};
+    use crate::test_utils::{self, CHAIN_ID};
+
+    fn temporary_db() -> sled::Db {
+        sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    // An empty DB at the given schema version
+    fn db_at(version: u64) -> sled::Db {
+        let db = temporary_db();
+        db.open_tree(METADATA_TREE).unwrap()
+            .insert(SCHEMA_VERSION_KEY, bincode::serialize(&version).unwrap())
+            .unwrap();
+        db
+    }
+
+    // A store at the given schema version, without running any migrations
+    fn store_at(version: u64) -> SledStore {
+        let db = db_at(version);
+        SledStore {
+            jobs: db.open_tree(JOBS_TREE).unwrap(),
+            proofs: db.open_tree(PROOFS_TREE).unwrap(),
+            inputs: db.open_tree(INPUTS_TREE).unwrap(),
+            job_ids: db.open_tree(JOB_IDS_TREE).unwrap(),
+            metadata: db.open_tree(METADATA_TREE).unwrap(),
+            db,
+        }
+    }
+
+    // The key of job(10) from before job keys had the chain ID
+    fn v1_key() -> Vec<u8> {
+        job_key(&test_utils::job(10))[..v1::JOB_KEY_SIZE].to_vec()
+    }
+
+    fn v1_record(status: JobStatus) -> v1::JobRecord {
+        v1::JobRecord {
+            job_id: "job-1".to_string(),
+            status,
+            proof_id: Some("request-1".to_string()),
+            attempts: 2,
+            created_at: 100,
+            updated_at: 200,
+        }
+    }
+
+    fn schema_version(store: &SledStore) -> u64 {
+        bincode::deserialize(&store.metadata.get(SCHEMA_VERSION_KEY).unwrap().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn sled_store() {
+        check_store(&SledStore::from_db(temporary_db(), None).unwrap());
+    }
+
+    #[test]
+    fn new_db_is_at_latest_version() {
+        let store = SledStore::from_db(temporary_db(), None).unwrap();
+        assert_eq!(schema_version(&store), LATEST_VERSION);
+    }
+
+    #[test]
+    fn refuses_newer_schema() {
+        let db = temporary_db();
+        db.open_tree(METADATA_TREE).unwrap()
+            .insert(SCHEMA_VERSION_KEY, bincode::serialize(&(LATEST_VERSION + 1)).unwrap())
+            .unwrap();
+        assert!(matches!(SledStore::from_db(db, Some(CHAIN_ID)), Err(StorageError::UnsupportedSchema(..))));
+    }
+
+    #[test]
+    fn migrates_v1_to_v2() {
+        let store = store_at(1);
+        let key = v1_key();
+        store.jobs.insert(&key, bincode::serialize(&v1_record(JobStatus::Failed("oops".to_string()))).unwrap()).unwrap();
+
+        migrate_v1_to_v2(&store, Some(CHAIN_ID)).unwrap();
+        // Bumped along with the records
+        assert_eq!(schema_version(&store), 2);
+
+        let record: JobRecord = bincode::deserialize(&store.jobs.get(&key).unwrap().unwrap()).unwrap();
+        assert_eq!(record.priority, Priority::Normal);
+        assert_eq!(record.job_id, "job-1");
+        assert_eq!(record.attempts, 2);
+        assert!(matches!(record.status, JobStatus::Failed(error) if error == "oops"));
+    }
+
+    #[test]
+    fn migrates_v2_to_v3() {
+        let store = store_at(2);
+        let old_key = v1_key();
+        let record = JobRecord::queued(&old_key, Priority::High);
+        store.jobs.insert(&old_key, bincode::serialize(&record).unwrap()).unwrap();
+        store.job_ids.insert(record.job_id.as_bytes(), old_key.as_slice()).unwrap();
+        store.proofs.insert(&old_key, b"proof".as_slice()).unwrap();
+        store.inputs.insert(&old_key, b"input".as_slice()).unwrap();
+
+        migrate_v2_to_v3(&store, Some(CHAIN_ID)).unwrap();
+        // Safe to run again, e.g. after being interrupted
+        migrate_v2_to_v3(&store, Some(CHAIN_ID)).unwrap();
+
+        let key = job_key(&test_utils::job(10));
+        assert_eq!(key, [old_key.as_slice(), CHAIN_ID.as_bytes()].concat());
+        let migrated = store.get_job(&key).unwrap().unwrap();
+        assert_eq!(migrated.job_id, record.job_id);
+        assert_eq!(migrated.priority, Priority::High);
+        assert_eq!(store.job_key_by_id(&record.job_id).unwrap(), Some(key.clone()));
+        assert_eq!(store.proofs.get(&key).unwrap().unwrap().as_ref(), b"proof");
+        assert_eq!(store.inputs.get(&key).unwrap().unwrap().as_ref(), b"input");
+        for tree in [&store.jobs, &store.proofs, &store.inputs] {
+            assert!(tree.get(&old_key).unwrap().is_none());
+        }
+        assert_eq!(store.jobs_from(Bound::Unbounded).count(), 1);
+    }
+
+    #[test]
+    fn migrates_v2_to_v3_only_with_legacy_chain_id() {
+        // Nothing to migrate, so nothing to ask for
+        migrate_v2_to_v3(&store_at(2), None).unwrap();
+
+        let store = store_at(2);
+        let old_key = v1_key();
+        store.jobs.insert(&old_key, bincode::serialize(&JobRecord::queued(&old_key, Priority::Normal)).unwrap()).unwrap();
+        assert!(matches!(migrate_v2_to_v3(&store, None), Err(StorageError::LegacyChainIdRequired)));
+        assert!(store.jobs.get(&old_key).unwrap().is_some());
+    }
+
+    #[test]
+    fn refuses_unversioned_db() {
+        let db = temporary_db();
+        db.insert(b"job", b"record".as_slice()).unwrap();
+        assert!(matches!(SledStore::from_db(db, Some(CHAIN_ID)), Err(StorageError::UnknownSchema)));
+    }
+
+    #[test]
+    fn refuses_to_open_legacy_db_without_legacy_chain_id() {
+        let db = db_at(2);
+        let old_key = v1_key();
+        db.open_tree(JOBS_TREE).unwrap()
+            .insert(&old_key, bincode::serialize(&JobRecord::queued(&old_key, Priority::Normal)).unwrap())
+            .unwrap();
+        assert!(matches!(SledStore::from_db(db.clone(), None), Err(StorageError::LegacyChainIdRequired)));
+
+        // Picks up where it stopped once it's given one
+        let store = SledStore::from_db(db, Some(CHAIN_ID)).unwrap();
+        assert_eq!(schema_version(&store), LATEST_VERSION);
+        let (_, job, _) = store.jobs_from(Bound::Unbounded).next().unwrap().unwrap();
+        assert_eq!(job.chain_id, CHAIN_ID);
+    }
+
+    #[test]
+    fn migrates_v1_to_latest_on_open() {
+        let db = db_at(1);
+        let old_key = v1_key();
+        db.open_tree(JOBS_TREE).unwrap()
+            .insert(&old_key, bincode::serialize(&v1_record(JobStatus::Queued)).unwrap())
+            .unwrap();
+
+        let store = SledStore::from_db(db, Some(CHAIN_ID)).unwrap();
+
+        assert_eq!(schema_version(&store), LATEST_VERSION);
+        let jobs: Vec<_> = store.jobs_from(Bound::Unbounded).map(Result::unwrap).collect();
+        assert_eq!(jobs.len(), 1);
+        let (key, job, record) = &jobs[0];
+        assert_eq!(job.chain_id, CHAIN_ID);
+        assert_eq!(job.height, 10);
+        assert!(matches!(record.status, JobStatus::Queued));
+        assert_eq!(record.priority, Priority::Normal);
+        assert_eq!(store.job_key_by_id(&record.job_id).unwrap().as_ref(), Some(key));
+    }
+}
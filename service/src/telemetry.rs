@@ -0,0 +1,51 @@
+use clap::ValueEnum;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+const SERVICE_NAME: &str = "eq-service";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/*
+    Logs go to stdout in `format`, filtered by RUST_LOG (info by default). With an OTLP
+    endpoint, spans are exported there as well, e.g. to a Jaeger or Tempo collector.
+    Every job gets a span with its job_id, so a single request can be followed from the
+    RPC through fetching and proving.
+*/
+pub fn init(format: LogFormat, otlp_endpoint: Option<&str>) -> Result<Option<TracerProvider>, Box<dyn std::error::Error>> {
+    let fmt_layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    };
+
+    let provider = otlp_endpoint
+        .map(|endpoint| -> Result<_, Box<dyn std::error::Error>> {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .build()?;
+            Ok(TracerProvider::builder()
+                .with_batch_exporter(exporter, runtime::Tokio)
+                .with_resource(Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)]))
+                .build())
+        })
+        .transpose()?;
+    let otel_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)));
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(fmt_layer)
+        .with(otel_layer)
+        .try_init()?;
+    // Kept by the caller so any spans still buffered can be flushed on the way out
+    Ok(provider)
+}
@@ -0,0 +1,128 @@
+// Shared by the unit tests
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use celestia_types::blob::{Blob, Commitment};
+use celestia_types::nmt::{Namespace, NamespaceProof};
+use celestia_types::ExtendedHeader;
+use nmt_rs::simple_merkle::proof::Proof;
+use sp1_sdk::{SP1Proof, SP1ProofWithPublicValues, SP1PublicValues, SP1Stdin};
+use tokio::sync::Semaphore;
+
+use eq_common::KeccakInclusionToDataRootProofInput;
+use crate::eqs::ProofMode;
+use crate::job::Job;
+use crate::node::{CelestiaNode, NodeError};
+use crate::prover::ProverNetwork;
+use crate::queue::{JobQueue, QueueLimits};
+
+pub const CHAIN_ID: &str = "test-1";
+
+pub fn namespace() -> Namespace {
+    Namespace::new_v0(&[1, 2, 3]).unwrap()
+}
+
+pub fn job(height: u64) -> Job {
+    Job {
+        height,
+        namespace: namespace().as_bytes().to_vec(),
+        commitment: vec![7; 32],
+        proof_mode: ProofMode::Groth16,
+        chain_id: CHAIN_ID.to_string(),
+    }
+}
+
+pub fn proof() -> SP1ProofWithPublicValues {
+    SP1ProofWithPublicValues {
+        proof: SP1Proof::Core(Vec::new()),
+        public_values: SP1PublicValues::from(&[1, 2, 3]),
+        sp1_version: "test".to_string(),
+    }
+}
+
+pub fn input() -> KeccakInclusionToDataRootProofInput {
+    KeccakInclusionToDataRootProofInput {
+        blob_data: vec![1, 2, 3],
+        blob_index: 0,
+        blob_namespace: namespace(),
+        nmt_multiproofs: Vec::new(),
+        row_root_multiproof: Proof { siblings: Vec::new(), range: 0..0 },
+        row_roots: Vec::new(),
+        data_root: vec![0; 32],
+        keccak_hash: [0; 32],
+    }
+}
+
+pub fn queue(capacity: usize) -> JobQueue {
+    JobQueue::new(QueueLimits {
+        capacity,
+        running: 4,
+        running_per_priority: HashMap::new(),
+    })
+}
+
+// A node that's up but has nothing, every request fails
+pub struct EmptyNode;
+
+#[tonic::async_trait]
+impl CelestiaNode for EmptyNode {
+    async fn local_head(&self) -> Result<ExtendedHeader, NodeError> {
+        Err(NodeError::Failed("No headers".to_string()))
+    }
+
+    async fn network_head(&self) -> Result<ExtendedHeader, NodeError> {
+        Err(NodeError::Failed("No headers".to_string()))
+    }
+
+    async fn header(&self, _height: u64) -> Result<ExtendedHeader, NodeError> {
+        Err(NodeError::Failed("No headers".to_string()))
+    }
+
+    async fn headers_between(&self, _from: &ExtendedHeader, _to: u64) -> Result<Vec<ExtendedHeader>, NodeError> {
+        Err(NodeError::Failed("No headers".to_string()))
+    }
+
+    async fn blob(&self, _height: u64, _namespace: Namespace, _commitment: Commitment) -> Result<Blob, NodeError> {
+        Err(NodeError::Failed("Blob not found".to_string()))
+    }
+
+    async fn blob_proof(&self, _height: u64, _namespace: Namespace, _commitment: Commitment) -> Result<Vec<NamespaceProof>, NodeError> {
+        Err(NodeError::Failed("Blob not found".to_string()))
+    }
+}
+
+// Hands out `proof` for every request, keeping track of what was requested
+#[derive(Default)]
+pub struct FakeProver {
+    pub requests: Mutex<Vec<ProofMode>>,
+    // Never answers requests, like a prover network that's stopped responding
+    pub hang: bool,
+    // If set, each request waits for a permit before it's answered
+    pub gate: Option<Arc<Semaphore>>,
+}
+
+#[tonic::async_trait]
+impl ProverNetwork for FakeProver {
+    async fn request_proof(&self, _stdin: &SP1Stdin, proof_mode: ProofMode) -> Result<String, String> {
+        let count = {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(proof_mode);
+            requests.len()
+        };
+        if self.hang {
+            std::future::pending::<()>().await;
+        }
+        if let Some(gate) = &self.gate {
+            gate.acquire().await.map_err(|e| e.to_string())?.forget();
+        }
+        Ok(format!("request-{}", count))
+    }
+
+    async fn wait_proof(&self, _request_id: &str) -> Result<SP1ProofWithPublicValues, String> {
+        Ok(proof())
+    }
+
+    async fn cycles(&self, _request_id: &str) -> Result<Option<u64>, String> {
+        Ok(Some(1000))
+    }
+}
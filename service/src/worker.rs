@@ -0,0 +1,655 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, Notify, RwLock, Semaphore};
+use tokio::task::AbortHandle;
+use tonic::Status;
+use tracing::{error, info, info_span, warn, Instrument};
+
+use celestia_types::nmt::Namespace;
+use celestia_types::blob::Commitment;
+use sp1_sdk::{SP1ProofWithPublicValues, SP1Stdin};
+
+use eq_common::{KeccakInclusionToDataRootProofInput, create_inclusion_proof_input};
+use crate::job::{self, Job, JobRecord, JobStatus, unix_now};
+use crate::metrics::{self, NODE_RPC_ERRORS, PROVER_CYCLES, STAGE_DURATION};
+use crate::node::{Networks, NodeError};
+use crate::prover::ProverNetwork;
+use crate::queue::{JobQueue, Running};
+use crate::storage::JobStore;
+
+// How long shutting down waits for prover requests that are underway once the drain timeout is up
+const SUBMISSION_GRACE: Duration = Duration::from_secs(30);
+// Jobs that couldn't reach the node are queued again after this, doubling with every attempt
+const RETRY_BACKOFF: Duration = Duration::from_secs(10);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(600);
+// After this many attempts (operator retries included) a job that can't reach the node fails
+const MAX_ATTEMPTS: u32 = 8;
+
+// Why working on a job stopped short
+#[derive(Debug, PartialEq)]
+enum JobError {
+    // Something the job depends on couldn't be reached, so it's worth trying again later
+    Retry(String),
+    Fail(String),
+}
+
+impl From<String> for JobError {
+    fn from(e: String) -> Self {
+        JobError::Fail(e)
+    }
+}
+
+impl From<NodeError> for JobError {
+    fn from(e: NodeError) -> Self {
+        match e {
+            NodeError::Unavailable(e) => JobError::Retry(e),
+            NodeError::Failed(e) => JobError::Fail(e),
+        }
+    }
+}
+
+// A job being worked on. Every run of a job gets a new generation, so a run that was
+// cancelled (and maybe retried since) can tell it no longer owns the job.
+struct InFlight {
+    generation: u64,
+    handle: AbortHandle,
+}
+
+// Takes queued jobs through fetching and proving, recording every transition in the DB
+#[derive(Clone)]
+pub struct Worker {
+    networks: Arc<Networks>,
+    storage: Arc<dyn JobStore>,
+    prover: Arc<dyn ProverNetwork>,
+    job_updates: broadcast::Sender<Vec<u8>>,
+    queue: Arc<JobQueue>,
+    // Limits how many jobs can be talking to the node at once
+    fetch_permits: Arc<Semaphore>,
+    // Jobs currently being worked on, by key. Whoever takes a job out of here (the job itself
+    // once it's done, or a cancel) records how it ended, but only if it hasn't ended already.
+    in_flight: Arc<Mutex<HashMap<Vec<u8>, InFlight>>>,
+    next_generation: Arc<AtomicU64>,
+    // Woken whenever a job leaves in_flight
+    job_finished: Arc<Notify>,
+    // Whether new prover requests can be made. Every request holds a read lock until it's
+    // recorded in the DB, so shutting down can wait for the ones underway.
+    submissions: Arc<RwLock<bool>>,
+    // Keys of the jobs whose prover requests are underway, so the ones shutting down gives up on can be named
+    submitting: Arc<Mutex<HashSet<Vec<u8>>>>,
+}
+
+impl Worker {
+    pub fn new(
+        networks: Arc<Networks>,
+        storage: Arc<dyn JobStore>,
+        prover: Arc<dyn ProverNetwork>,
+        job_updates: broadcast::Sender<Vec<u8>>,
+        queue: Arc<JobQueue>,
+        max_concurrent_fetches: usize,
+    ) -> Self {
+        Worker {
+            networks,
+            storage,
+            prover,
+            job_updates,
+            queue,
+            fetch_permits: Arc::new(Semaphore::new(max_concurrent_fetches)),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            next_generation: Arc::new(AtomicU64::new(0)),
+            job_finished: Arc::new(Notify::new()),
+            submissions: Arc::new(RwLock::new(true)),
+            submitting: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    // Starts queued jobs until `shutdown` is set, jobs that are still queued then stay queued in the DB
+    pub async fn run(self, mut shutdown: watch::Receiver<bool>) {
+        loop {
+            let (key, job, running) = tokio::select! {
+                popped = self.queue.pop() => popped,
+                _ = shutdown.wait_for(|&stop| stop) => return,
+            };
+            // The job may have been cancelled while it was waiting in the queue
+            let record = match self.storage.get_job(&key) {
+                Ok(Some(record)) if !record.status.is_terminal() => record,
+                Ok(_) => continue,
+                Err(e) => {
+                    error!(height = job.height, "Failed to load job: {}", e);
+                    continue;
+                }
+            };
+            let mut in_flight = self.in_flight.lock().unwrap();
+            // Already being worked on, e.g. an operator retried it while it was waiting to be requeued
+            if in_flight.contains_key(&key) {
+                continue;
+            }
+            // Everything logged while working on the job is tagged with its ID
+            let span = info_span!("job", job_id = record.job_id.as_str(), chain_id = job.chain_id.as_str(), height = job.height, proof_mode = ?job.proof_mode);
+            let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+            let handle = tokio::spawn(self.clone().process(job, key.clone(), generation, running).instrument(span));
+            in_flight.insert(key, InFlight { generation, handle: handle.abort_handle() });
+        }
+    }
+
+    // Whether `generation` is still the run working on the job, taking it out of in_flight if so
+    fn finish_run(&self, key: &[u8], generation: u64) -> bool {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        match in_flight.get(key) {
+            Some(run) if run.generation == generation => {
+                in_flight.remove(key);
+                self.job_finished.notify_one();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Holds on to `running` until the job is done, so it counts against the concurrency limits
+    async fn process(self, job: Job, key: Vec<u8>, generation: u64, _running: Running) {
+        let result = self.prove(&job, &key, generation).await;
+
+        // Cancelled while we were finishing up, the cancel records the outcome
+        if !self.finish_run(&key, generation) {
+            return;
+        }
+        // Store the proof before marking the job complete, so a complete job always has one
+        let status = match result {
+            Ok(proof) => match self.storage.put_proof(&key, &proof) {
+                Ok(()) => JobStatus::Completed,
+                Err(e) => JobStatus::Failed(format!("Failed to store proof: {}", e)),
+            },
+            Err(JobError::Retry(error)) => match self.retry_delay(&key) {
+                Some(delay) => {
+                    warn!("Job will be retried in {:?}: {}", delay, error);
+                    match self.set_status_if(&key, JobStatus::Queued, unfinished) {
+                        Ok(true) => self.requeue_after(job, key, delay),
+                        Ok(false) => {}
+                        Err(e) => error!("Failed to requeue job: {}", e),
+                    }
+                    return;
+                }
+                None => JobStatus::Failed(error),
+            },
+            Err(JobError::Fail(error)) => JobStatus::Failed(error),
+        };
+        match &status {
+            JobStatus::Failed(error) => warn!("Job failed: {}", error),
+            _ => info!("Job completed"),
+        }
+        if let Err(e) = self.set_status_if(&key, status, unfinished) {
+            error!("Failed to record result of job: {}", e);
+        }
+    }
+
+    // How long to wait before trying the job again, None once it has used up its attempts
+    fn retry_delay(&self, key: &[u8]) -> Option<Duration> {
+        let attempts = self.storage.get_job(key).ok()??.attempts;
+        if attempts >= MAX_ATTEMPTS {
+            return None;
+        }
+        Some((RETRY_BACKOFF * 2u32.pow(attempts.saturating_sub(1))).min(MAX_RETRY_BACKOFF))
+    }
+
+    // Puts the job back in the queue after `delay`, unless it has been cancelled or retried in the meantime
+    fn requeue_after(&self, job: Job, key: Vec<u8>, delay: Duration) {
+        let worker = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            match worker.storage.get_job(&key) {
+                // Raising the priority while the job waits only updates the record, so take it from there
+                Ok(Some(record)) if matches!(record.status, JobStatus::Queued) => worker.queue.push(job, record.priority),
+                Ok(_) => {}
+                Err(e) => error!("Failed to load job to requeue: {}", e),
+            }
+        }.in_current_span());
+    }
+
+    // Stops working on the job and marks it as failed. There's no way to cancel a request
+    // on the prover network, so one that was already submitted will still run to completion there.
+    pub fn cancel(&self, key: &[u8]) -> Result<JobRecord, Status> {
+        let record = self.storage.get_job(key)?
+            .ok_or_else(|| Status::not_found("Job not found"))?;
+        if record.status.is_terminal() {
+            return Err(Status::failed_precondition("Job has already finished"));
+        }
+
+        let run = self.in_flight.lock().unwrap().remove(key);
+        if let Some(run) = run {
+            run.handle.abort();
+            self.job_finished.notify_one();
+        }
+        self.queue.remove(key);
+        // It may have finished since we looked
+        if !self.set_status_if(key, JobStatus::Failed("Cancelled by operator".to_string()), unfinished).map_err(Status::internal)? {
+            return Err(Status::failed_precondition("Job has already finished"));
+        }
+        self.storage.get_job(key)?
+            .ok_or_else(|| Status::not_found("Job not found"))
+    }
+
+    async fn prove(&self, job: &Job, key: &[u8], generation: u64) -> Result<SP1ProofWithPublicValues, JobError> {
+        // A job that already has a prover request (i.e. from before a restart) only needs to wait for it
+        let record = self.storage.get_job(key).map_err(|e| e.to_string())?;
+        let (request_id, proving_since) = match record {
+            Some(JobRecord { status: JobStatus::Proving(request_id), updated_at, .. }) => (request_id, updated_at),
+            _ => (self.request_proof(job, key, generation).await?, unix_now()),
+        };
+
+        let proof = self.prover.wait_proof(&request_id).await?;
+        let proof_mode = metrics::proof_mode_label(Some(job.proof_mode));
+        STAGE_DURATION
+            .with_label_values(&["proving", &proof_mode])
+            .observe(unix_now().saturating_sub(proving_since) as f64);
+        // Only for the metrics, a failure here shouldn't fail the job
+        match self.prover.cycles(&request_id).await {
+            Ok(Some(cycles)) => PROVER_CYCLES.with_label_values(&[&proof_mode]).observe(cycles as f64),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to get cycle count: {}", e),
+        }
+        Ok(proof)
+    }
+
+    async fn request_proof(&self, job: &Job, key: &[u8], generation: u64) -> Result<String, JobError> {
+        // Cancelled between leaving the queue and starting
+        if !self.set_status_if(key, JobStatus::Fetching, unfinished)? {
+            return Err(JobError::Fail("Job has already finished".to_string()));
+        }
+
+        // Reuse the input from an earlier attempt if we have it, the node may have pruned the data since
+        let inclusion_proof_input = match self.storage.get_input(key).map_err(|e| e.to_string())? {
+            Some(input) => input,
+            None => {
+                let _permit = self.fetch_permits.acquire()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let input = self.fetch_input(job).await?;
+                self.storage.put_input(key, &input)
+                    .map_err(|e| format!("Failed to store proof input: {}", e))?;
+                input
+            }
+        };
+
+        let mut stdin = SP1Stdin::new();
+        stdin.write(&inclusion_proof_input);
+
+        // Once the prover network has accepted the request we have to record it, or we'd lose
+        // track of a proof we're paying for. So that's done in a task of its own, which stopping
+        // the job doesn't interrupt and shutting down waits for.
+        let accepting = self.submissions.clone().read_owned().await;
+        if !*accepting {
+            return Err(JobError::Fail("Service is shutting down".to_string()));
+        }
+        let worker = self.clone();
+        let key = key.to_vec();
+        let proof_mode = job.proof_mode;
+        self.submitting.lock().unwrap().insert(key.clone());
+        tokio::spawn(async move {
+            let _accepting = accepting;
+            let request_id = worker.prover.request_proof(&stdin, proof_mode).await;
+            worker.submitting.lock().unwrap().remove(&key);
+            let request_id = request_id?;
+            // A job that was cancelled in the meantime stays failed, and if it was retried since
+            // the new run makes its own request
+            let current = worker.in_flight.lock().unwrap()
+                .get(&key)
+                .is_some_and(|run| run.generation == generation);
+            if current {
+                worker.set_status_if(&key, JobStatus::Proving(request_id.clone()), |record| matches!(record.status, JobStatus::Fetching))?;
+            }
+            Ok::<_, String>(request_id)
+        }.in_current_span())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(JobError::Fail)
+    }
+
+    // Gives running jobs `timeout` to finish, then stops the rest where they are. Every step of a
+    // job is recorded as it's made, so a stopped job picks up from there on the next start, e.g.
+    // one that was already submitted only waits for its proof. Prover requests that are underway
+    // get SUBMISSION_GRACE more to be recorded, after that they're abandoned. Their jobs are still
+    // fetching as far as the DB knows, so they're requested again on the next start, possibly
+    // paying for a second proof. Call after `run` has been stopped.
+    pub async fn drain(&self, timeout: Duration) {
+        let all_finished = async {
+            loop {
+                let finished = self.job_finished.notified();
+                if self.in_flight.lock().unwrap().is_empty() {
+                    return;
+                }
+                finished.await;
+            }
+        };
+        if tokio::time::timeout(timeout, all_finished).await.is_err() {
+            // Left in in_flight, so prover requests that are underway still get recorded
+            let in_flight = self.in_flight.lock().unwrap();
+            warn!(jobs = in_flight.len(), "Stopping unfinished jobs, they'll resume on the next start");
+            for run in in_flight.values() {
+                run.handle.abort();
+            }
+        }
+        match tokio::time::timeout(SUBMISSION_GRACE, self.submissions.write()).await {
+            Ok(mut accepting) => *accepting = false,
+            Err(_) => {
+                for key in self.submitting.lock().unwrap().iter() {
+                    warn!(job_id = job::job_id(key).as_str(), "Abandoning prover request that's still underway, the job will be requested again on the next start");
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn fetch_input(&self, job: &Job) -> Result<KeccakInclusionToDataRootProofInput, JobError> {
+        let commitment = Commitment::new(
+            job.commitment.clone()
+                .try_into()
+                .map_err(|_| "Invalid commitment".to_string())?
+        );
+        let namespace = Namespace::from_raw(&job.namespace)
+            .map_err(|e| e.to_string())?;
+        let node = self.networks.node(&job.chain_id)
+            .ok_or_else(|| format!("No Celestia node configured for chain {}", job.chain_id))?;
+        let proof_mode = metrics::proof_mode_label(Some(job.proof_mode));
+        let node_error = |method: &str| {
+            NODE_RPC_ERRORS.with_label_values(&[method, &proof_mode]).inc();
+            node.report_error();
+        };
+
+        let fetch_started = Instant::now();
+        let client = node.client().await?;
+        let blob = client.blob(job.height, namespace, commitment)
+            .instrument(info_span!("celestia_rpc", method = "blob.Get"))
+            .await
+            .inspect_err(|_| node_error("blob.Get"))
+            .map_err(|e| e.context("Failed to get blob"))?;
+
+        // Get the ExtendedHeader
+        let header = client.header(job.height)
+            .instrument(info_span!("celestia_rpc", method = "header.GetByHeight"))
+            .await
+            .inspect_err(|_| node_error("header.GetByHeight"))
+            .map_err(|e| e.context("Failed to get header"))?;
+        // The node is checked when we connect, but the proof must be for the network that was asked for
+        if header.header.chain_id.as_str() != job.chain_id {
+            return Err(JobError::Fail(format!("Header is from chain {}, expected {}", header.header.chain_id, job.chain_id)));
+        }
+        node.verify_header(client.as_ref(), &header)
+            .await
+            .map_err(|e| e.context("Header failed verification"))?;
+
+        let nmt_multiproofs = client
+            .blob_proof(job.height, namespace, commitment)
+            .instrument(info_span!("celestia_rpc", method = "blob.GetProof"))
+            .await
+            .inspect_err(|_| node_error("blob.GetProof"))
+            .map_err(|e| e.context("Failed to get blob proof"))?;
+        STAGE_DURATION
+            .with_label_values(&["node_fetch", &proof_mode])
+            .observe(fetch_started.elapsed().as_secs_f64());
+
+        let build_started = Instant::now();
+        // eq-common stays free of tracing since the guest depends on it, so the span goes here
+        let input = info_span!("input_build")
+            .in_scope(|| create_inclusion_proof_input(&blob, &header, nmt_multiproofs))
+            .map_err(|e| e.to_string())?;
+        STAGE_DURATION
+            .with_label_values(&["input_build", &proof_mode])
+            .observe(build_started.elapsed().as_secs_f64());
+        Ok(input)
+    }
+
+    pub fn set_status(&self, key: &[u8], status: JobStatus) -> Result<(), String> {
+        let mut record = self.storage.get_job(key)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Job record is missing".to_string())?;
+        record.update(status);
+        self.storage.put_job(key, &record).map_err(|e| e.to_string())?;
+        // Nobody watching is fine
+        let _ = self.job_updates.send(key.to_vec());
+        Ok(())
+    }
+
+    // Like set_status, but only if `applies` to the record as it is when written, returning whether it did
+    fn set_status_if(&self, key: &[u8], status: JobStatus, applies: impl Fn(&JobRecord) -> bool) -> Result<bool, String> {
+        loop {
+            let current = self.storage.get_job(key)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| "Job record is missing".to_string())?;
+            if !applies(&current) {
+                return Ok(false);
+            }
+            let mut record = current.clone();
+            record.update(status.clone());
+            if self.storage.replace_job(key, &current, &record).map_err(|e| e.to_string())? {
+                let _ = self.job_updates.send(key.to_vec());
+                return Ok(true);
+            }
+        }
+    }
+}
+
+fn unfinished(record: &JobRecord) -> bool {
+    !record.status.is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eqs::Priority;
+    use crate::node::NodeClient;
+    use crate::storage::{self, MemoryStore};
+    use crate::test_utils::{self, EmptyNode, FakeProver, CHAIN_ID};
+
+    struct Setup {
+        worker: Worker,
+        prover: Arc<FakeProver>,
+        shutdown: watch::Sender<bool>,
+    }
+
+    fn setup() -> Setup {
+        setup_with(NodeClient::connected(CHAIN_ID, Arc::new(EmptyNode)), FakeProver::default())
+    }
+
+    fn setup_with(node: NodeClient, prover: FakeProver) -> Setup {
+        let prover = Arc::new(prover);
+        let worker = Worker::new(
+            Arc::new(Networks::new(vec![Arc::new(node)])),
+            Arc::new(MemoryStore::new()),
+            prover.clone(),
+            broadcast::channel(16).0,
+            Arc::new(test_utils::queue(10)),
+            1,
+        );
+        let (shutdown, stop) = watch::channel(false);
+        tokio::spawn(worker.clone().run(stop));
+        Setup { worker, prover, shutdown }
+    }
+
+    // Stores the job as queued, without handing it to the worker yet
+    fn add_job(worker: &Worker, height: u64) -> (Job, Vec<u8>) {
+        let job = test_utils::job(height);
+        let key = storage::job_key(&job);
+        worker.storage.put_job(&key, &JobRecord::queued(&key, Priority::Normal)).unwrap();
+        (job, key)
+    }
+
+    async fn finished(worker: &Worker, key: &[u8]) -> JobRecord {
+        let mut updates = worker.job_updates.subscribe();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let record = worker.storage.get_job(key).unwrap().unwrap();
+                if record.status.is_terminal() {
+                    return record;
+                }
+                let _ = updates.recv().await;
+            }
+        })
+        .await
+        .expect("Job didn't finish")
+    }
+
+    #[tokio::test]
+    async fn proves_job_with_stored_input() {
+        let Setup { worker, prover, shutdown } = setup();
+        let (job, key) = add_job(&worker, 10);
+        worker.storage.put_input(&key, &test_utils::input()).unwrap();
+        let proof_mode = job.proof_mode;
+        worker.queue.push(job, Priority::Normal);
+
+        let record = finished(&worker, &key).await;
+        assert!(matches!(record.status, JobStatus::Completed));
+        assert_eq!(record.proof_id.as_deref(), Some("request-1"));
+        assert_eq!(record.attempts, 1);
+        assert!(worker.storage.get_proof(&key).unwrap().is_some());
+        assert_eq!(*prover.requests.lock().unwrap(), vec![proof_mode]);
+        shutdown.send_replace(true);
+    }
+
+    #[tokio::test]
+    async fn fails_job_the_node_cant_serve() {
+        let Setup { worker, prover, shutdown } = setup();
+        let (job, key) = add_job(&worker, 10);
+        worker.queue.push(job, Priority::Normal);
+
+        let record = finished(&worker, &key).await;
+        assert!(matches!(&record.status, JobStatus::Failed(error) if error.starts_with("Failed to get blob")));
+        assert!(worker.storage.get_input(&key).unwrap().is_none());
+        assert!(prover.requests.lock().unwrap().is_empty());
+        shutdown.send_replace(true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_job_while_node_is_unreachable() {
+        // Never connects, since there are no URLs
+        let node = NodeClient::new(CHAIN_ID.to_string(), Vec::new(), None, None);
+        let Setup { worker, prover, shutdown } = setup_with(node, FakeProver::default());
+        let (job, key) = add_job(&worker, 10);
+        worker.queue.push(job, Priority::Normal);
+
+        let wait_until = |done: fn(&JobRecord) -> bool| {
+            let worker = worker.clone();
+            let key = key.clone();
+            async move {
+                loop {
+                    let record = worker.storage.get_job(&key).unwrap().unwrap();
+                    if done(&record) {
+                        return record;
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        };
+        let record = wait_until(|record| record.attempts >= 2 || record.status.is_terminal()).await;
+        assert!(!record.status.is_terminal());
+
+        // Until it has used up its attempts
+        let record = wait_until(|record| record.status.is_terminal()).await;
+        assert!(matches!(&record.status, JobStatus::Failed(error) if error == "Not connected to a Celestia node"));
+        assert_eq!(record.attempts, MAX_ATTEMPTS);
+        assert!(prover.requests.lock().unwrap().is_empty());
+        shutdown.send_replace(true);
+    }
+
+    #[tokio::test]
+    async fn resumes_submitted_job() {
+        let Setup { worker, prover, shutdown } = setup();
+        let (job, key) = add_job(&worker, 10);
+        worker.set_status(&key, JobStatus::Proving("request-7".to_string())).unwrap();
+        worker.queue.push(job, Priority::Normal);
+
+        let record = finished(&worker, &key).await;
+        assert!(matches!(record.status, JobStatus::Completed));
+        assert_eq!(record.proof_id.as_deref(), Some("request-7"));
+        assert!(prover.requests.lock().unwrap().is_empty());
+        shutdown.send_replace(true);
+    }
+
+    #[tokio::test]
+    async fn cancels_queued_job() {
+        let Setup { worker, shutdown, .. } = setup();
+        // Not running, so the job stays in the queue
+        shutdown.send_replace(true);
+        let (job, key) = add_job(&worker, 10);
+        worker.queue.push(job, Priority::Normal);
+
+        let record = worker.cancel(&key).unwrap();
+        assert!(matches!(&record.status, JobStatus::Failed(error) if error == "Cancelled by operator"));
+        assert_eq!(worker.queue.len(), 0);
+        assert_eq!(worker.cancel(&key).unwrap_err().code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stale_prover_request_leaves_retried_job_alone() {
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let Setup { worker, prover, shutdown } = setup_with(
+            NodeClient::connected(CHAIN_ID, Arc::new(EmptyNode)),
+            FakeProver { gate: Some(gate.clone()), ..Default::default() },
+        );
+        let (job, key) = add_job(&worker, 10);
+        worker.storage.put_input(&key, &test_utils::input()).unwrap();
+        worker.queue.push(job.clone(), Priority::Normal);
+        let requested = |count| {
+            let prover = prover.clone();
+            async move {
+                while prover.requests.lock().unwrap().len() < count {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        };
+        requested(1).await;
+
+        // Retried like Admin.RetryJob does, while the first request is still underway
+        worker.cancel(&key).unwrap();
+        worker.set_status(&key, JobStatus::Queued).unwrap();
+        worker.queue.push(job, Priority::Normal);
+        requested(2).await;
+
+        // The first request is answered first, but belongs to the cancelled run
+        gate.add_permits(1);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let record = worker.storage.get_job(&key).unwrap().unwrap();
+        assert!(matches!(record.status, JobStatus::Fetching));
+        assert_eq!(record.proof_id, None);
+
+        gate.add_permits(1);
+        let record = finished(&worker, &key).await;
+        assert!(matches!(record.status, JobStatus::Completed));
+        assert_eq!(record.proof_id.as_deref(), Some("request-2"));
+        shutdown.send_replace(true);
+    }
+
+    #[tokio::test]
+    async fn no_prover_requests_after_drain() {
+        let Setup { worker, prover, shutdown } = setup();
+        shutdown.send_replace(true);
+        worker.drain(Duration::from_secs(1)).await;
+        let (job, key) = add_job(&worker, 10);
+        worker.storage.put_input(&key, &test_utils::input()).unwrap();
+
+        let error = worker.request_proof(&job, &key, 0).await.unwrap_err();
+        assert_eq!(error, JobError::Fail("Service is shutting down".to_string()));
+        assert!(prover.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_abandons_stuck_prover_requests() {
+        let Setup { worker, prover, shutdown } = setup_with(
+            NodeClient::connected(CHAIN_ID, Arc::new(EmptyNode)),
+            FakeProver { hang: true, ..Default::default() },
+        );
+        let (job, key) = add_job(&worker, 10);
+        worker.storage.put_input(&key, &test_utils::input()).unwrap();
+        worker.queue.push(job, Priority::Normal);
+        while prover.requests.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Returns even though the prover never answers
+        shutdown.send_replace(true);
+        worker.drain(Duration::from_secs(1)).await;
+        assert!(worker.submitting.lock().unwrap().contains(&key));
+        // Nothing was recorded, so the job is requested again on the next start
+        assert!(matches!(worker.storage.get_job(&key).unwrap().unwrap().status, JobStatus::Fetching));
+    }
+}
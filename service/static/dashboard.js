@@ -0,0 +1,187 @@
+// Talks to the HTTP gateway, see service/src/gateway.rs for the routes
+const REFRESH_MS = 10000;
+
+const apiKeyInput = document.getElementById("api-key");
+apiKeyInput.value = localStorage.getItem("apiKey") || "";
+apiKeyInput.addEventListener("change", () => {
+    localStorage.setItem("apiKey", apiKeyInput.value);
+    refresh();
+});
+
+// Listing jobs shows every client's, so it's an admin route. Only kept for the browser session.
+const adminTokenInput = document.getElementById("admin-token");
+adminTokenInput.value = sessionStorage.getItem("adminToken") || "";
+adminTokenInput.addEventListener("change", () => {
+    sessionStorage.setItem("adminToken", adminTokenInput.value);
+    loadJobs(false);
+});
+
+async function api(path) {
+    const headers = {};
+    if (apiKeyInput.value) headers["x-api-key"] = apiKeyInput.value;
+    if (adminTokenInput.value) headers["x-admin-token"] = adminTokenInput.value;
+    const response = await fetch(path, { headers });
+    const body = await response.json();
+    if (!response.ok) {
+        throw new Error(body.error || response.statusText);
+    }
+    return body;
+}
+
+function shortHex(hex) {
+    return hex.length > 18 ? `${hex.slice(0, 10)}…${hex.slice(-6)}` : hex;
+}
+
+function fillCounts(element, counts) {
+    element.replaceChildren(...Object.entries(counts).flatMap(([name, count]) => {
+        const term = document.createElement("dt");
+        term.textContent = name;
+        const value = document.createElement("dd");
+        value.textContent = count;
+        return [term, value];
+    }));
+}
+
+function fillNodes(element, nodes) {
+    element.replaceChildren(...Object.entries(nodes).flatMap(([chainId, node]) => {
+        const term = document.createElement("dt");
+        term.textContent = chainId;
+        const value = document.createElement("dd");
+        value.textContent = node.error
+            ? `Unreachable: ${node.error}`
+            : `${node.synced ? "Synced" : "Syncing"} at ${node.local_height} of ${node.network_height}`;
+        value.className = node.error ? "error" : node.synced ? "ok" : "warn";
+        return [term, value];
+    }));
+}
+
+async function refreshStatus() {
+    const nodeStatus = document.getElementById("node-status");
+    try {
+        const status = await api("/v1/status");
+        fillNodes(nodeStatus, status.nodes);
+        fillCounts(document.getElementById("job-counts"), { ...status.jobs, "waiting to start": status.queue_length });
+        fillCounts(document.getElementById("prover-requests"), status.prover_requests);
+    } catch (e) {
+        const error = document.createElement("dd");
+        error.textContent = e.message;
+        error.className = "error";
+        nodeStatus.replaceChildren(error);
+    }
+}
+
+let stages = "";
+let nextPageToken = null;
+
+async function loadJobs(append) {
+    const params = new URLSearchParams({ page_size: 50 });
+    if (stages) params.set("stages", stages);
+    if (append && nextPageToken) params.set("page_token", nextPageToken);
+
+    const tbody = document.getElementById("jobs");
+    try {
+        const page = await api(`/v1/jobs?${params}`);
+        const rows = page.jobs.map(job => {
+            const row = document.createElement("tr");
+            const cells = [
+                job.chain_id,
+                job.height,
+                shortHex(job.namespace),
+                job.proof_mode,
+                job.priority,
+                job.stage,
+                job.attempts,
+                new Date(job.updated_at * 1000).toLocaleString(),
+            ];
+            for (const value of cells) {
+                const cell = document.createElement("td");
+                cell.textContent = value;
+                row.appendChild(cell);
+            }
+            row.className = job.stage;
+            row.addEventListener("click", () => showJob(job.job_id));
+            return row;
+        });
+        if (append) {
+            tbody.append(...rows);
+        } else {
+            tbody.replaceChildren(...rows);
+        }
+        nextPageToken = page.next_page_token;
+        document.getElementById("load-more").hidden = !nextPageToken;
+    } catch (e) {
+        const row = document.createElement("tr");
+        row.innerHTML = `<td colspan="8" class="error"></td>`;
+        row.firstChild.textContent = e.message;
+        tbody.replaceChildren(row);
+    }
+}
+
+async function showJob(jobId) {
+    const details = document.getElementById("job-details");
+    const download = document.getElementById("download-proof");
+    document.getElementById("job-id").textContent = jobId;
+    details.hidden = false;
+    try {
+        const job = await api(`/v1/jobs/${jobId}`);
+        document.getElementById("job-json").textContent = JSON.stringify(job, null, 2);
+        download.hidden = job.stage !== "complete";
+        download.onclick = () => downloadProof(jobId);
+    } catch (e) {
+        document.getElementById("job-json").textContent = e.message;
+        download.hidden = true;
+    }
+    details.scrollIntoView({ behavior: "smooth" });
+}
+
+async function downloadProof(jobId) {
+    try {
+        const proof = await api(`/v1/jobs/${jobId}/proof`);
+        const blob = new Blob([JSON.stringify(proof, null, 2)], { type: "application/json" });
+        const link = document.createElement("a");
+        link.href = URL.createObjectURL(blob);
+        link.download = `proof-${jobId}.json`;
+        link.click();
+        URL.revokeObjectURL(link.href);
+    } catch (e) {
+        alert(`Failed to download proof: ${e.message}`);
+    }
+}
+
+document.getElementById("submit-form").addEventListener("submit", async event => {
+    event.preventDefault();
+    const form = new FormData(event.target);
+    const result = document.getElementById("submit-result");
+    const params = new URLSearchParams({ proof_mode: form.get("proof_mode"), priority: form.get("priority") });
+    if (form.get("chain_id")) params.set("chain_id", form.get("chain_id"));
+    try {
+        const response = await api(`/v1/keccak-inclusion/${form.get("height")}/${form.get("namespace")}/${form.get("commitment")}?${params}`);
+        const position = response.queue_position != null ? `, ${response.queue_position} jobs ahead of it` : "";
+        result.textContent = `Job ${response.job_id} is ${response.status}${position}`;
+        result.className = response.status === "failed" ? "error" : "ok";
+        loadJobs(false);
+    } catch (e) {
+        result.textContent = e.message;
+        result.className = "error";
+    }
+});
+
+for (const tab of document.querySelectorAll("#stage-tabs button")) {
+    tab.addEventListener("click", () => {
+        stages = tab.dataset.stages;
+        for (const other of document.querySelectorAll("#stage-tabs button")) {
+            other.classList.toggle("active", other === tab);
+        }
+        loadJobs(false);
+    });
+}
+document.querySelector("#stage-tabs button").classList.add("active");
+document.getElementById("load-more").addEventListener("click", () => loadJobs(true));
+
+function refresh() {
+    refreshStatus();
+    loadJobs(false);
+}
+
+refresh();
+setInterval(refreshStatus, REFRESH_MS);